@@ -0,0 +1,67 @@
+//! Timings for some `lib/data/list.w` workloads, for spotting
+//! interpreter slowdowns (or speedups). Run with `cargo bench`.
+//!
+//! Each workload runs as a script after the prelude,
+//! and the time taken by the prelude alone is subtracted.
+
+use std::time::{ Duration, Instant };
+use worst::base::*;
+use worst::builtins;
+use worst::reader::Reader;
+
+// n range -> [0 1 ... n-1]
+const SETUP: &str = "
+define range [
+    const n
+    [] 0 while (clone n i64-compare -1 i64-equal) [
+        const i i list-push i 1 i64-add
+    ] drop
+]
+define repeat [
+    upquote const count
+    upquote updo current-defenv value-set-defenv const body
+    count while [ clone 0 i64-compare 1 i64-equal ] [
+        body eval 1 i64-sub
+    ] drop
+]
+";
+
+const WORKLOADS: &[(&str, &str)] = &[
+    ("list-map", "1000 range const l repeat 5 [ l list-map [ 1 i64-add ] drop ]"),
+    ("list-find-first-index",
+     "1000 range const l repeat 5 [ l list-find-first-index [ 999 equal ] drop ]"),
+    ("list-deduplicate-sequential",
+     "1000 range list-map [ 3 i64-div ] const l repeat 2 [ l list-deduplicate-sequential drop ]"),
+    ("list-set-differsection",
+     "100 range const a a list-map [ 2 i64-mul ] const b
+      a b list-set-differsection drop drop drop"),
+];
+
+fn run(script: &str) -> Duration {
+    let mut i = worst::embedded();
+    builtins::install(&mut i);
+    let code = Reader::new(i.uniques_mut())
+        .read_all(&mut format!("{SETUP}\n{script}").chars()).unwrap();
+    // after the prelude
+    let mut body = List::from(code);
+    body.prepend(std::mem::take(i.body_mut()));
+    *i.body_mut() = body;
+    let start = Instant::now();
+    let res = i.run();
+    let elapsed = start.elapsed();
+    assert!(res.is_ok(), "workload failed: {script}");
+    elapsed
+}
+
+// best of a few runs
+fn time(script: &str) -> Duration {
+    (0..3).map(|_| run(script)).min().unwrap()
+}
+
+fn main() {
+    let base = time("");
+    println!("{:<30} {:>10.2?}", "(prelude)", base);
+    for (name, script) in WORKLOADS {
+        println!("{:<30} {:>10.2?}", name, time(script).saturating_sub(base));
+    }
+}
@@ -0,0 +1,56 @@
+//! Compile the code in `lib/` (see `src/compiled.rs`)
+//! so the embedded copy doesn't need to be read at startup.
+
+#![allow(dead_code, unused_imports)]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Just enough of the crate to read and compile code
+#[path = "src"]
+mod src {
+    pub mod base;
+    pub mod compiled;
+    pub mod reader;
+}
+use src::{ base, compiled, reader };
+
+fn main() {
+    for path in ["lib", "src/base", "src/base.rs", "src/compiled.rs", "src/reader.rs"] {
+        println!("cargo:rerun-if-changed={path}");
+    }
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("compiled");
+    let _ = fs::remove_dir_all(&out);
+    fs::create_dir_all(&out).unwrap();
+    // Features aren't cfgs in a build script, only environment variables.
+    // Without the compiled files, wasm builds read the embedded code as usual.
+    let embed = env::var_os("CARGO_FEATURE_ENABLE_FS_EMBED").is_some();
+    let wasm = env::var_os("CARGO_FEATURE_WASM").is_some();
+    if embed && !wasm {
+        compile_dir(Path::new("lib"), "", &out);
+    }
+}
+
+fn compile_dir(lib: &Path, prefix: &str, out: &Path) {
+    for entry in fs::read_dir(lib.join(prefix)).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name().into_string().unwrap();
+        let name = if prefix.is_empty() { name } else { format!("{prefix}/{name}") };
+        if entry.file_type().unwrap().is_dir() {
+            fs::create_dir_all(out.join(&name)).unwrap();
+            compile_dir(lib, &name, out);
+        } else if let Some(stem) = name.strip_suffix(".w") {
+            let src = fs::read_to_string(entry.path()).unwrap();
+            let uniques = base::UniqueGen::default();
+            let mut reader = reader::Reader::new(&uniques);
+            // same as load-embedded
+            reader.set_source_name(&name);
+            // anything unreadable is left to fail when it's used
+            if let Ok(code) = reader.read_all(&mut src.chars()) {
+                let code = compiled::encode(&base::List::from(code), &uniques).unwrap();
+                fs::write(out.join(format!("{stem}.wc")), code).unwrap();
+            }
+        }
+    }
+}
@@ -1,67 +1,33 @@
 
-; hashmap: hash values into an i64map with equality collision avoidance
+; hashmap and hashset are builtin (see make-hashmap, make-hashset).
+; Keys can be any value, compared with value-equal and value-hash
+; (so meta is ignored, and 1 and 1.0 are different keys).
 
-; alias the type (necessary?)
-<i64map> const <hashmap>
-export <hashmap>
-
-define make-hashmap [ make-i64map ]
-export make-hashmap
-
-; k v make-cell
-define make-cell [ [] swap list-push swap list-push ]
-define cell-key [ 0 list-get ]
-define cell-val [ 1 list-get ]
-
-; hashmap key v hashmap-insert => hashmap
-define (with-dynamics (value-hash equal)) hashmap-insert [
-    const v const k const hashmap
-    ; linear probe
-    k value-hash
-    ge? 0 if [ -1 ] [ 1 ] const next-hash ; towards 0
-    while [
-        const h
-        hashmap h i64map-get false? if [
-            ; free slot
-            drop h #f
-        ] [
-            cell-key k equal if [
-                ; overwrite this slot
-                h #f
-            ] [
-                ; try next slot
-                h next-hash add #t
-            ]
-        ]
-    ] []
-    const kh
-    hashmap kh
-    k v make-cell
-    i64map-insert
+; [ k1 v1 k2 v2 ... ] pairs->hashmap => hashmap
+define pairs->hashmap [
+    make-hashmap swap
+    while [ list-empty? not ] [
+        list-pop const k
+        list-pop const v
+        swap k v hashmap-insert swap
+    ]
+    drop
 ]
-export hashmap-insert
+export pairs->hashmap
 
-; hashmap key hashmap-get => value|#f
-define (with-dynamics (value-hash equal)) hashmap-get [
-    const k const hashmap
-    ; linear probe
-    k value-hash
-    ge? 0 if [ -1 ] [ 1 ] const next-hash ; towards 0
-    while [
-        const h
-        hashmap h i64map-get false? if [
-            ; not here
-            drop #f #f
-        ] [
-            clone cell-key k equal if [
-                ; found it
-                cell-val #f
-            ] [
-                ; next slot
-                drop h next-hash add #t
-            ]
-        ]
-    ] []
+; hashmap hashmap->pairs => [ k1 v1 k2 v2 ... ]
+define hashmap->pairs [
+    const m
+    [] m hashmap-keys list-iter [
+        const k
+        k list-push m k hashmap-get list-push
+    ]
+    list-reverse
 ]
-export hashmap-get
+export hashmap->pairs
 
+; [ v1 v2 ... ] list->hashset => hashset
+define list->hashset [
+    make-hashset swap list-iter [ hashset-insert ]
+]
+export list->hashset
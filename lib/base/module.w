@@ -118,7 +118,7 @@ define module-import [
                     "import: unknown type" error
                 ]
             ]
-            read-string->list
+            imported-name <symbol> is-type if [ symbol->string ] [] read-cached-string->list
             imported-name eval-module-list->defenv const env
             module-cache place-get imported-name env hashmap-insert
             module-cache swap place-set drop
@@ -152,7 +152,7 @@ define export [
         exports list-iter [
             const x
             export-env x defenv-lookup false? if [
-                "export: not defined: " x value->string string-append
+                "export: not defined: {}" [] x list-push format
                 clone println error
             ] [ ]
             const def
@@ -6,19 +6,24 @@ define is-type2 [
     t is-type if [ b t is-type ] [ b #f ]
 ]
 
-define equal [ drop drop #f ]
-define (dispatch (<i64> is-type2)) equal [ i64-equal ]
-define (dispatch (<f64> is-type2)) equal [ f64-equal ]
-define (dispatch (<string> is-type2)) equal [ string-equal ]
-define (dispatch (<symbol> is-type2)) equal [ symbol-equal ]
-define (dispatch (<bool> is-type2)) equal [ bool-equal ]
+; structural, so lists are equal if their items are
+define equal [ value-equal ]
+; numbers of different types are unified first, as in compare
+define (with-dynamics (equal) dispatch (numbers-mixed?)) equal [ numbers-unify equal ]
 export equal
 
 define equal? [ clone2 updo equal ]
 export equal?
 
+; anything else is in value-compare order
+define compare [ value-compare ]
+; numbers of different types are compared as the higher one in the tower
+; i64 < bigint < rational < f64 (see numbers-unify)
+define (with-dynamics (compare) dispatch (numbers-mixed?)) compare [ numbers-unify compare ]
 define (dispatch (<i64> is-type2)) compare [ i64-compare ]
 define (dispatch (<f64> is-type2)) compare [ f64-compare ]
+define (dispatch (<bigint> is-type2)) compare [ bigint-compare ]
+define (dispatch (<rational> is-type2)) compare [ rational-compare ]
 define (dispatch (<string> is-type2)) compare [ string-compare ]
 export compare
 
@@ -43,14 +48,27 @@ export lt?
 export ge?
 export gt?
 
+; mixed numbers are converted up the tower like compare
+define (with-dynamics (add) dispatch (numbers-mixed?)) add [ numbers-unify add ]
 define (dispatch (<i64> is-type2)) add [ i64-add ]
 define (dispatch (<f64> is-type2)) add [ f64-add ]
+define (dispatch (<bigint> is-type2)) add [ bigint-add ]
+define (dispatch (<rational> is-type2)) add [ rational-add ]
+define (with-dynamics (sub) dispatch (numbers-mixed?)) sub [ numbers-unify sub ]
 define (dispatch (<i64> is-type2)) sub [ i64-sub ]
 define (dispatch (<f64> is-type2)) sub [ f64-sub ]
+define (dispatch (<bigint> is-type2)) sub [ bigint-sub ]
+define (dispatch (<rational> is-type2)) sub [ rational-sub ]
+define (with-dynamics (mul) dispatch (numbers-mixed?)) mul [ numbers-unify mul ]
 define (dispatch (<i64> is-type2)) mul [ i64-mul ]
 define (dispatch (<f64> is-type2)) mul [ f64-mul ]
+define (dispatch (<bigint> is-type2)) mul [ bigint-mul ]
+define (dispatch (<rational> is-type2)) mul [ rational-mul ]
+define (with-dynamics (div) dispatch (numbers-mixed?)) div [ numbers-unify div ]
 define (dispatch (<i64> is-type2)) div [ i64-div ]
 define (dispatch (<f64> is-type2)) div [ f64-div ]
+define (dispatch (<bigint> is-type2)) div [ bigint-div ]
+define (dispatch (<rational> is-type2)) div [ rational-div ]
 export add
 export sub
 export mul
@@ -58,11 +76,15 @@ export div
 
 define (<i64> type-dispatch) negate [ i64-negate ]
 define (<f64> type-dispatch) negate [ f64-negate ]
+define (<bigint> type-dispatch) negate [ bigint-negate ]
+define (<rational> type-dispatch) negate [ rational-negate ]
 export negate
 
 define abs [ lt? 0 if [negate] [] ]
 define (<i64> type-dispatch) abs [ i64-abs ]
 define (<f64> type-dispatch) abs [ f64-abs ]
+define (<bigint> type-dispatch) abs [ bigint-abs ]
+define (<rational> type-dispatch) abs [ rational-abs ]
 export abs
 
 define max [ clone2 lt if [swap] [] drop ]
@@ -78,67 +100,24 @@ export bool-or
 
 define (<list> type-dispatch) length [ list-length ]
 define (<bytevector> type-dispatch) length [ bytevector-length ]
+define (<string> type-dispatch) length [ string-length ]
 export length
 
 define (dispatch (<list> is-type2)) append [ list-append ]
 define (dispatch (<string> is-type2)) append [ string-append ]
 export append
 
-define value-hash [ drop #f bool-hash ] ; the default hash is that of false
-define (<bool> type-dispatch) value-hash [ bool-hash ]
-define (<symbol> type-dispatch) value-hash [ symbol-hash ]
-define (<string> type-dispatch) value-hash [ string-hash ]
-define (<bytevector> type-dispatch) value-hash [ bytevector-hash ]
-define (<unique> type-dispatch) value-hash [ unique-hash ]
-define (<i64> type-dispatch) value-hash [ i64-hash ]
-export value-hash
-
-define value->string [drop "<value>"]
+; v value->string => string : for showing values to people.
+; Strings are shown as they are, and anything else as value->readable
+; unless there is a type-dispatch definition for it
+; (which print-value and println-value will also use).
+define value->string [ value->readable ]
 define (<string> type-dispatch) value->string []
-define (<bool> type-dispatch) value->string [if ["#t"] ["#f"]]
-define (<symbol> type-dispatch) value->string [symbol->string]
-define (<i64> type-dispatch) value->string [i64->string]
-define (<f64> type-dispatch) value->string [f64->string]
-define (<unique> type-dispatch) value->string [drop "<unique>"]
-define (<type-id> type-dispatch) value->string [
-    <string> type-id->unique value-meta-entry false? if [ drop "<type>" ] [
-        "<" swap ">" string-append string-append
-    ]
-]
-define (<interpreter> type-dispatch) value->string [drop "<interpreter>"]
-define (<i64map> type-dispatch) value->string [drop "<i64map>"]
-; define (dispatch (file-port?)) value->string [drop "<file-port>"]
-; define (dispatch (embedded-file-port?)) value->string [drop "<embedded-file-port>"]
-
-define (<builtin> type-dispatch) value->string [
-    drop "<builtin>"
-    ; builtin-name false? if [ drop "<builtin>" ] [
-    ;     value->string "<builtin " swap string-append ">" string-append
-    ; ]
-]
-
-define (with-dynamics (value->string) <list> type-dispatch) value->string [
-    "(" "" dig list-iter [
-        value->string
-        ; concat accumulator with either "" or previous trailing " "
-        bury string-append swap
-        string-append " "
-    ]
-    ; drop trailing " " or semi-sacrificial ""
-    drop ")" string-append
-]
-
-;define (dispatch (bytevector?)) value->string [
-;    length const len
-;    "[" len value->string append " bytes]" append
-;    swap drop
-;]
-
 export value->string
 
-define print-value [ value->string print ]
+define (with-dynamics (value->string)) print-value [ value->string print ]
 export print-value
-define println-value [ value->string println ]
+define (with-dynamics (value->string)) println-value [ value->string println ]
 export println-value
 
 ; not an op, but required just below
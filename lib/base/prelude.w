@@ -75,18 +75,16 @@ define export [
     upquote const name
     name updo definition-resolve false? if [
         drop
-        "export: not defined: " name symbol->string string-append
+        "export: not defined: {}" [] name list-push format
         println error
     ] [ ]
     name quote definition-add quote uplevel uplevel
 ]
 ; load-embedded "path/to/embedded/file.w"
 define load-embedded [
-    upquote
-    string->fs-path
-    embedded-file-open
-    embedded-file-port->string
-    read-string->list
+    upquote const path
+    path string->fs-path
+    embedded-file-read->list
     updo current-defenv
     defenv-new-locals
     value-set-defenv
@@ -97,7 +95,7 @@ define load-embedded [
 load-embedded "base/attribute.w"
 ; predicate dispatch attribute
 load-embedded "base/dispatch.w"
-; equal, compare, add/sub/etc, length, value->string - things using dispatch
+; equal, compare, add/sub/etc, length - things using dispatch
 load-embedded "base/ops.w"
 
 ; doc attribute
@@ -114,6 +112,28 @@ define do [ upquote updo eval ]
 define list-empty? [clone list-length 0 equal]
 define error? [clone <is-error> type-id->unique value-meta-entry not not]
 
+; catch [ body ] [ handler ]
+; evaluate body, but if it raises an error, put it on the stack and do handler
+define catch [
+    updo current-defenv defenv-new-locals const env
+    upquote env value-set-defenv const body
+    upquote env value-set-defenv const handler
+    body eval-catch
+    false? if [ drop [] ] [ handler ] uplevel
+]
+
+; finally [ body ] [ cleanup ]
+; evaluate body and then cleanup,
+; even if body raises an error (which is raised again afterwards)
+define finally [
+    updo current-defenv defenv-new-locals const env
+    upquote env value-set-defenv const body
+    upquote env value-set-defenv const cleanup
+    body eval-catch const err
+    cleanup uplevel
+    err false? if [ drop ] [ error ]
+]
+
 define read-line [ stdin-port-read-line ]
 
 ; required by import module cache
@@ -133,23 +153,3 @@ load-embedded "cli/repl.w"
 ; anything above this line is in the default module environment
 ; this should also be the last load-embedded as it redefines export
 load-embedded "base/module.w"
-
-command-line-arguments list-pop drop ; $0
-list-empty? if [
-    drop
-    worst-repl
-] [
-    list-pop const path const args
-    path
-    string->fs-path
-    file-open-options file-open-options-set-read
-    file-open
-    error? if [
-        drop args path string->symbol cli-module-run
-    ] [
-        ; jank to get ui/cli and import "relative.w" working
-        path const current-script-path
-        read-port->list eval
-    ]
-]
-
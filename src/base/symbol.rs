@@ -1,22 +1,78 @@
-
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::{ Debug, Display };
+use std::hash::{ Hash, Hasher };
+use std::rc::{ Rc, Weak };
 
 use super::value::*;
 
 /// Symbol type: an unquoted word used to look up definitions.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Symbol {
-    v: String,
-}
+///
+/// Symbols are interned: every live symbol with the same name
+/// (on a given thread) shares one allocation, so comparing and hashing
+/// symbols doesn't need to look at the name at all.
+///
+/// The table of names only holds weak references, and a name is taken
+/// out of it when the last symbol with that name is dropped,
+/// so making symbols from data (e.g. with `string->symbol`) doesn't leak.
+/// It is per-thread rather than per-interpreter because symbols are made
+/// all over (by the reader, by Rust code with [to_symbol](ToSymbol::to_symbol))
+/// usually without an interpreter at hand.
+/// Symbols (like all values) can't be sent to another thread,
+/// and snapshots and compiled code store names rather than ids,
+/// so ids never need to agree between threads or processes.
+#[derive(Clone)]
+pub struct Symbol(Rc<Interned>);
 impl Value for Symbol {}
 
+struct Interned {
+    name: Rc<str>,
+}
+
+impl Drop for Interned {
+    fn drop(&mut self) {
+        // the last one with this name, so forget it
+        // (unless the table is already gone at thread exit)
+        let _ = INTERNED.try_with(|interned| {
+            if let Ok(mut interned) = interned.try_borrow_mut() {
+                interned.remove(&self.name);
+            }
+        });
+    }
+}
+
+thread_local! {
+    // Every live symbol's name on this thread (see Symbol).
+    static INTERNED: RefCell<HashMap<Rc<str>, Weak<Interned>>> = RefCell::default();
+}
+
+impl Symbol {
+    /// A number identifying this symbol's name while any symbol with
+    /// that name exists. Only meaningful on the current thread,
+    /// and may be reused once all symbols with the name are gone.
+    pub fn id(&self) -> usize { Rc::as_ptr(&self.0) as usize }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool { Rc::ptr_eq(&self.0, &other.0) }
+}
+impl Eq for Symbol {}
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.id().hash(state); }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("Symbol").field(&self.0.name).finish()
+    }
+}
 impl Display for Symbol {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.v)
+        write!(f, "{}", self.0.name)
     }
 }
 impl AsRef<str> for Symbol {
-    fn as_ref(&self) -> &str { self.v.as_ref() }
+    fn as_ref(&self) -> &str { self.0.name.as_ref() }
 }
 
 /// Conversion into a symbol.
@@ -31,13 +87,60 @@ impl<T: Into<Symbol>> ToSymbol for T {
 }
 
 impl From<Symbol> for String {
-    fn from(s: Symbol) -> Self { s.v }
+    fn from(s: Symbol) -> Self { s.0.name.to_string() }
 }
 
 impl From<String> for Symbol {
-    fn from(v: String) -> Symbol { Symbol { v } }
+    fn from(v: String) -> Symbol { Symbol::from(v.as_str()) }
 }
 impl From<&str> for Symbol {
-    fn from(s: &str) -> Symbol { Symbol::from(s.to_string()) }
+    fn from(s: &str) -> Symbol {
+        INTERNED.with(|interned| {
+            let mut interned = interned.borrow_mut();
+            if let Some(sym) = interned.get(s).and_then(Weak::upgrade) {
+                return Symbol(sym);
+            }
+            let name = Rc::<str>::from(s);
+            let sym = Rc::new(Interned { name: name.clone() });
+            interned.insert(name, Rc::downgrade(&sym));
+            Symbol(sym)
+        })
+    }
+}
+impl From<&String> for Symbol {
+    fn from(v: &String) -> Symbol { Symbol::from(v.as_str()) }
 }
+impl From<&Symbol> for Symbol {
+    fn from(s: &Symbol) -> Symbol { s.clone() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interned() {
+        let a = Symbol::from("interned-test");
+        let b = Symbol::from(String::from("interned-test"));
+        let c = Symbol::from("interned-test-other");
+        assert_eq!(a, b);
+        assert_eq!(a.id(), b.id());
+        assert_ne!(a, c);
+        assert_eq!(b.as_ref(), "interned-test");
+        assert_eq!(String::from(c), "interned-test-other");
+    }
 
+    #[test]
+    fn forgotten() {
+        let count = || INTERNED.with(|i| i.borrow().len());
+        let before = count();
+        let a = Symbol::from("forgotten-test");
+        let b = a.clone();
+        assert_eq!(count(), before + 1);
+        drop(a);
+        assert_eq!(count(), before + 1);
+        drop(b);
+        assert_eq!(count(), before);
+        assert_eq!(Symbol::from("forgotten-test").as_ref(), "forgotten-test");
+    }
+}
@@ -9,6 +9,7 @@ impl Value for bool {}
 impl Value for String {}
 impl Value for i64 {}
 impl Value for f64 {}
+impl Value for num_bigint::BigInt {} // integers beyond i64
 impl Value for Vec<u8> {} // bytevector
 impl Value for TypeId {} // type-id
 
@@ -33,6 +34,9 @@ impl Place {
 
     /// Get a copy of the contained [Val].
     pub fn get(&self) -> Val { self.0.try_borrow().unwrap().clone() }
+
+    // Address of the shared cell, to spot shared places when serialising.
+    pub(crate) fn addr(&self) -> usize { Rc::as_ptr(&self.0) as usize }
 }
 
 /// Meta value signalling that the value represents some kind of error.
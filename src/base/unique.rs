@@ -5,7 +5,8 @@ use std::cell::RefCell;
 use im_rc::HashMap;
 
 /// A globally unique value, guaranteed to only be equal to itself or its clones.
-#[derive(Clone, PartialEq, Eq, Hash)]
+/// Ordered by when they were created.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Unique {
     id: usize,
     is_type: bool,
@@ -46,6 +47,12 @@ impl UniqueGen {
         self.0.borrow().types.get(&TypeId::of::<T>()).cloned()
     }
 
+    /// Find the [Unique] associated with the given [TypeId],
+    /// if one has been added.
+    pub fn lookup_type_id(&self, ty: TypeId) -> Option<Unique> {
+        self.0.borrow().types.get(&ty).cloned()
+    }
+
     /// Get or create the [Unique] associated with [T].
     pub fn get_type<T: 'static>(&mut self) -> Unique {
         self.get_type_id(TypeId::of::<T>())
@@ -69,6 +69,17 @@ impl Val {
         (*self.v).type_id()
     }
 
+    /// Whether the two values share the same inner value
+    /// (i.e. one is a copy of the other), regardless of meta.
+    pub fn ptr_eq(&self, other: &Val) -> bool {
+        Rc::ptr_eq(&self.v, &other.v)
+    }
+
+    // Address of the inner value, to spot shared values when serialising.
+    pub(crate) fn addr(&self) -> usize {
+        Rc::as_ptr(&self.v) as *const () as usize
+    }
+
     /// Get a reference to the inner value, if it is of the given type.
     pub fn downcast_ref<T: Value>(&self) -> Option<&T> {
         self.v.downcast_ref::<T>()
@@ -99,6 +110,17 @@ impl Meta {
             .and_then(|r| r.downcast_ref::<T>())
     }
 
+    /// Iterate over all entries. As with [get_val](Meta::get_val),
+    /// the values have no meta of their own.
+    pub fn iter(&self) -> impl Iterator<Item=(&Unique, Val)> {
+        self.data.iter().map(|(u, v)| (u, Val { v: v.clone(), meta: Meta::default() }))
+    }
+
+    // Whether both are copies of the same metadata, entries and all.
+    pub(crate) fn ptr_eq(&self, other: &Meta) -> bool {
+        self.data.ptr_eq(&other.data)
+    }
+
     /// Check whether the metadata contains the given [Unique].
     pub fn contains_val(&self, u: &Unique) -> bool {
         self.data.contains_key(u)
@@ -5,11 +5,16 @@ use crate::interpreter::*;
 
 pub mod util;
 
+pub mod bignum;
 pub mod bytevector;
+pub mod compare;
 pub mod core;
 pub mod defenv;
 pub mod define;
+pub mod error;
+pub mod format;
 pub mod fs;
+pub mod hashmap;
 pub mod i64map;
 pub mod interpreter;
 pub mod list;
@@ -17,20 +22,27 @@ pub mod numeric;
 #[cfg(feature = "enable_os")]
 pub mod os;
 pub mod place;
+pub mod random;
 #[cfg(feature = "enable_process")]
 pub mod process;
 pub mod reader;
+pub mod regex;
 #[cfg(feature = "enable_stdio")]
 pub mod stdio;
 pub mod string;
 
 /// Define all enabled builtins in the given [Interpreter].
 pub fn install(i: &mut Interpreter) {
+    bignum::install(i);
     bytevector::install(i);
+    compare::install(i);
     core::install(i);
     defenv::install(i);
     define::install(i);
+    error::install(i);
+    format::install(i);
     fs::install(i);
+    hashmap::install(i);
     i64map::install(i);
     interpreter::install(i);
     list::install(i);
@@ -38,11 +50,22 @@ pub fn install(i: &mut Interpreter) {
     #[cfg(feature = "enable_os")]
     os::install(i);
     place::install(i);
+    random::install(i);
     #[cfg(feature = "enable_process")]
     process::install(i);
     reader::install(i);
+    regex::install(i);
     #[cfg(feature = "enable_stdio")]
     stdio::install(i);
     string::install(i);
 }
 
+
+/// All enabled builtins, as defined by [install],
+/// in a [DefEnv] sharing [UniqueGen](crate::base::UniqueGen) with `i`.
+/// Useful for [Interpreter::restore_snapshot].
+pub fn all_defenv(i: &Interpreter) -> DefEnv {
+    let mut inner = i.new_inner_empty();
+    install(&mut inner);
+    inner.defenv_ref().clone()
+}
@@ -0,0 +1,253 @@
+//! Arbitrary-precision integers ([BigInt]) and exact rationals ([BigRational])
+//!
+//! Results are always as small as they can be:
+//! a [BigInt] that fits is an [i64],
+//! and a [BigRational] with a denominator of 1 is an integer.
+//! So values of different types are never equal.
+//!
+//! Numbers of different types can be converted up the numeric tower
+//! (i64, bigint, rational, f64) with `numbers-unify`.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+use super::util;
+use crate::base::*;
+use crate::interpreter::*;
+
+impl Value for BigRational {}
+
+/// Make an integer [Val], as an [i64] if it fits or else a [BigInt].
+pub fn integer_val(n: BigInt) -> Val {
+    match i64::try_from(&n) {
+        Ok(n) => n.into(),
+        Err(_) => n.into(),
+    }
+}
+
+/// Make a rational [Val], as an integer (see [integer_val]) if it is one.
+pub fn rational_val(r: BigRational) -> Val {
+    if r.is_integer() {
+        integer_val(r.to_integer())
+    } else {
+        r.into()
+    }
+}
+
+// Position in the numeric tower, if it's a number.
+fn rank(v: &Val) -> Option<u8> {
+    if v.is::<i64>() { Some(0) }
+    else if v.is::<BigInt>() { Some(1) }
+    else if v.is::<BigRational>() { Some(2) }
+    else if v.is::<f64>() { Some(3) }
+    else { None }
+}
+
+fn to_bigint(v: &Val) -> Option<BigInt> {
+    if let Some(n) = v.downcast_ref::<i64>() { Some(BigInt::from(*n)) }
+    else { v.downcast_ref::<BigInt>().cloned() }
+}
+
+fn to_rational(v: &Val) -> Option<BigRational> {
+    to_bigint(v).map(BigRational::from_integer)
+        .or_else(|| v.downcast_ref::<BigRational>().cloned())
+}
+
+fn to_f64(v: &Val) -> Option<f64> {
+    if let Some(n) = v.downcast_ref::<i64>() { Some(*n as f64) }
+    else if let Some(n) = v.downcast_ref::<BigInt>() { n.to_f64() }
+    else if let Some(r) = v.downcast_ref::<BigRational>() { r.to_f64() }
+    else { v.downcast_ref::<f64>().copied() }
+}
+
+// Convert v to the type at the given rank, without meta.
+fn promote(v: &Val, rank: u8) -> Option<Val> {
+    match rank {
+        0 => v.downcast_ref::<i64>().map(|n| (*n).into()),
+        1 => to_bigint(v).map(Val::from),
+        2 => to_rational(v).map(Val::from),
+        _ => to_f64(v).map(Val::from),
+    }
+}
+
+/// `a b numbers-mixed? -> a b bool` :
+/// whether a and b are both numbers, but of different types.
+pub fn numbers_mixed(i: &mut Interpreter) -> BuiltinRet {
+    let b = i.stack_pop_val()?;
+    let a = i.stack_top_val()?;
+    let mixed = matches!((rank(&a), rank(&b)), (Some(ra), Some(rb)) if ra != rb);
+    i.stack_push(b);
+    i.stack_push(mixed);
+    Ok(())
+}
+
+/// `a b numbers-unify -> a b` :
+/// convert whichever number is lower in the numeric tower
+/// (i64, bigint, rational, f64) to the type of the other.
+pub fn numbers_unify(i: &mut Interpreter) -> BuiltinRet {
+    let b = i.stack_pop_val()?;
+    let a = i.stack_pop_val()?;
+    let (Some(ra), Some(rb)) = (rank(&a), rank(&b)) else {
+        let l = List::from(vec![a, b]);
+        return Err(i.make_error("not-numbers", l));
+    };
+    let top = ra.max(rb);
+    let a = if ra < top { promote(&a, top).unwrap() } else { a };
+    let b = if rb < top { promote(&b, top).unwrap() } else { b };
+    i.stack_push(a);
+    i.stack_push(b);
+    Ok(())
+}
+
+fn binop<T: Value + Clone>(f: impl Fn(T, T) -> Val) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let b = i.stack_pop::<T>()?.into_inner();
+        let a = i.stack_pop::<T>()?.into_inner();
+        i.stack_push(f(a, b));
+        Ok(())
+    }
+}
+
+fn unop<T: Value + Clone>(f: impl Fn(T) -> Val) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let a = i.stack_pop::<T>()?.into_inner();
+        i.stack_push(f(a));
+        Ok(())
+    }
+}
+
+// Like binop but gives a false error? when dividing by zero.
+fn divop<T: Value + Clone + Zero>(f: impl Fn(T, T) -> Val) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let b = i.stack_pop::<T>()?.into_inner();
+        let a = i.stack_pop::<T>()?.into_inner();
+        if b.is_zero() {
+            i.stack_push_error(false);
+        } else {
+            i.stack_push(f(a, b));
+        }
+        Ok(())
+    }
+}
+
+fn to_string<T: Value + ToString>(i: &mut Interpreter) -> BuiltinRet {
+    let a = i.stack_pop::<T>()?;
+    i.stack_push(a.as_ref().to_string());
+    Ok(())
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    util::add_const_type_builtin::<BigInt>(i, "<bigint>");
+    util::add_const_type_builtin::<BigRational>(i, "<rational>");
+
+    i.add_builtin("numbers-mixed?", numbers_mixed);
+    i.add_builtin("numbers-unify", numbers_unify);
+
+    i.add_builtin("bigint->string", to_string::<BigInt>);
+    i.add_builtin("bigint->f64", unop::<BigInt>(|a| a.to_f64().unwrap_or(f64::NAN).into()));
+    i.add_builtin("bigint-equal", util::equality::<BigInt>);
+    i.add_builtin("bigint-compare", util::comparison::<BigInt>);
+    i.add_builtin("bigint-hash", util::value_hash::<BigInt>);
+    i.add_builtin("bigint-add", binop::<BigInt>(|a, b| integer_val(a + b)));
+    i.add_builtin("bigint-sub", binop::<BigInt>(|a, b| integer_val(a - b)));
+    i.add_builtin("bigint-mul", binop::<BigInt>(|a, b| integer_val(a * b)));
+    i.add_builtin("bigint-div", divop::<BigInt>(|a, b| integer_val(a / b)));
+    i.add_builtin("bigint-remainder", divop::<BigInt>(|a, b| integer_val(a % b)));
+    i.add_builtin("bigint-negate", unop::<BigInt>(|a| integer_val(-a)));
+    i.add_builtin("bigint-abs", unop::<BigInt>(|a| integer_val(a.abs())));
+
+    // n d make-rational -> rational|integer, for integers n and d
+    i.add_builtin("make-rational", |i: &mut Interpreter| {
+        let d = i.stack_pop_val()?;
+        let n = i.stack_pop_val()?;
+        let (Some(n), Some(d)) = (to_bigint(&n), to_bigint(&d)) else {
+            let l = List::from(vec![n, d]);
+            return Err(i.make_error("not-integers", l));
+        };
+        if d.is_zero() {
+            i.stack_push_error(false);
+        } else {
+            i.stack_push(rational_val(BigRational::new(n, d)));
+        }
+        Ok(())
+    });
+    i.add_builtin("rational-numerator", unop::<BigRational>(|a| integer_val(a.numer().clone())));
+    i.add_builtin("rational-denominator", unop::<BigRational>(|a| integer_val(a.denom().clone())));
+    i.add_builtin("rational->string", to_string::<BigRational>);
+    i.add_builtin("rational->f64", unop::<BigRational>(|a| a.to_f64().unwrap_or(f64::NAN).into()));
+    i.add_builtin("rational-equal", util::equality::<BigRational>);
+    i.add_builtin("rational-compare", util::comparison::<BigRational>);
+    i.add_builtin("rational-hash", util::value_hash::<BigRational>);
+    i.add_builtin("rational-add", binop::<BigRational>(|a, b| rational_val(a + b)));
+    i.add_builtin("rational-sub", binop::<BigRational>(|a, b| rational_val(a - b)));
+    i.add_builtin("rational-mul", binop::<BigRational>(|a, b| rational_val(a * b)));
+    i.add_builtin("rational-div", divop::<BigRational>(|a, b| rational_val(a / b)));
+    i.add_builtin("rational-negate", unop::<BigRational>(|a| rational_val(-a)));
+    i.add_builtin("rational-abs", unop::<BigRational>(|a| rational_val(a.abs())));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builtins::numeric;
+
+    fn run(code: Vec<Val>) -> Interpreter {
+        let mut i = Interpreter::new(code);
+        install(&mut i);
+        numeric::install(&mut i);
+        assert!(i.run().is_ok());
+        i
+    }
+
+    fn sym(s: &str) -> Val { s.to_symbol().into() }
+
+    #[test]
+    fn overflow_promotes() {
+        let mut i = run(vec![
+            i64::MAX.into(), 1.into(), sym("i64-add"),
+            i64::MAX.into(), 1.into(), sym("i64-add"), (-1).into(), sym("numbers-unify"), sym("bigint-add"),
+            i64::MIN.into(), sym("i64-negate"),
+            i64::MIN.into(), (-1).into(), sym("i64-div"),
+            1.into(), 0.into(), sym("i64-remainder"),
+        ]);
+        // false error
+        assert!(!i.stack_pop::<bool>().ok().unwrap().into_inner());
+        let big = BigInt::from(i64::MAX) + 1;
+        assert_eq!(i.stack_pop::<BigInt>().ok().unwrap().as_ref(), &big);
+        assert_eq!(i.stack_pop::<BigInt>().ok().unwrap().as_ref(), &big);
+        // demoted back to i64
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), i64::MAX);
+    }
+
+    #[test]
+    fn rationals() {
+        let mut i = run(vec![
+            1.into(), 3.into(), sym("make-rational"),
+            1.into(), 6.into(), sym("make-rational"),
+            sym("rational-add"),
+            2.into(), 3.into(), sym("make-rational"),
+            3.into(), sym("numbers-unify"), sym("rational-mul"),
+            1.into(), 0.into(), sym("make-rational"),
+        ]);
+        // false error
+        assert!(!i.stack_pop::<bool>().ok().unwrap().into_inner());
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 2);
+        let half = i.stack_pop::<BigRational>().ok().unwrap();
+        assert_eq!(half.as_ref(), &BigRational::new(1.into(), 2.into()));
+    }
+
+    #[test]
+    fn mixed() {
+        let mut i = run(vec![
+            1.into(), 2.5.into(), sym("numbers-mixed?"),
+            1.into(), 2.into(), sym("numbers-mixed?"),
+        ]);
+        assert!(!i.stack_pop::<bool>().ok().unwrap().into_inner());
+        i.stack_pop_val().ok().unwrap();
+        i.stack_pop_val().ok().unwrap();
+        assert!(i.stack_pop::<bool>().ok().unwrap().into_inner());
+        i.stack_pop::<f64>().ok().unwrap();
+        i.stack_pop::<i64>().ok().unwrap();
+    }
+}
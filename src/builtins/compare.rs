@@ -0,0 +1,391 @@
+//! Structural equality, ordering and hashing of values, ignoring meta
+//! (except for registered keys, see below).
+//!
+//! Values are ordered first by kind:
+//! bools, numbers, strings, symbols, bytevectors, lists, uniques, type-ids,
+//! and then anything else (by type).
+//! Numbers of any type are ordered by their value,
+//! and then by type (i64, bigint, rational, f64) so that 1 and 1.0 aren't equal.
+//! f64s are in total order (see `f64-total-compare`),
+//! except that `0.0` is equal to `-0.0` and all NaNs are equal.
+//! Lists are compared item by item.
+//!
+//! Other types can [register] how they are compared and hashed.
+//! If they haven't, values are only equal to copies of themselves.
+//!
+//! Types made in Worst (values tagged with a meta entry for some unique)
+//! can instead [register_key] (or `value-compare-key-register`)
+//! to be compared and hashed by a key derived from each value.
+//! Such values are only equal to others with the same tag and an equal key,
+//! and are ordered after everything else (by tag, then key).
+
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::HashMap as StdHashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use crate::base::*;
+use crate::interpreter::*;
+use super::numeric::f64_bits;
+
+/// How to compare and hash values of a type not known to [value_compare].
+/// Both functions are only given values of the registered type.
+#[derive(Clone, Copy)]
+pub struct CompareHooks {
+    /// A total order (where [Ordering::Equal] means equal).
+    pub compare: fn(&Val, &Val) -> Ordering,
+    /// Hash consistently with `compare`.
+    pub hash: fn(&Val, &mut dyn Hasher),
+}
+
+/// Gives the key to compare a tagged value by (see [register_key]),
+/// or [None] to use the value itself.
+pub type KeyFn = Rc<dyn Fn(&Val) -> Option<Val>>;
+
+thread_local! {
+    static HOOKS: RefCell<StdHashMap<TypeId, CompareHooks>> = RefCell::default();
+    static KEYS: RefCell<BTreeMap<Unique, KeyFn>> = RefCell::default();
+}
+
+/// Set how values of the given type are compared and hashed
+/// (for all interpreters on the current thread).
+pub fn register_hooks(ty: TypeId, hooks: CompareHooks) {
+    HOOKS.with(|h| h.borrow_mut().insert(ty, hooks));
+}
+
+/// Compare and hash values of type [T] using its [Ord] and [Hash].
+pub fn register<T: Value + Ord + Hash>() {
+    fn compare<T: Value + Ord>(a: &Val, b: &Val) -> Ordering {
+        a.downcast_ref::<T>().cmp(&b.downcast_ref::<T>())
+    }
+    fn hash<T: Value + Hash>(v: &Val, mut state: &mut dyn Hasher) {
+        v.downcast_ref::<T>().hash(&mut state)
+    }
+    register_hooks(TypeId::of::<T>(), CompareHooks { compare: compare::<T>, hash: hash::<T> });
+}
+
+fn hooks(ty: TypeId) -> Option<CompareHooks> {
+    HOOKS.with(|h| h.borrow().get(&ty).copied())
+}
+
+/// Set how values with a meta entry for `tag` are compared and hashed:
+/// by the key that `key` gives for them (see the [module docs](self))
+/// (for all interpreters on the current thread).
+/// Register before using such values as hashmap keys.
+pub fn register_key(tag: Unique, key: KeyFn) {
+    KEYS.with(|k| k.borrow_mut().insert(tag, key));
+}
+
+// The registered tag of a value (the first one if it has several)
+// and its key.
+fn keyed(v: &Val) -> Option<(Unique, Val)> {
+    if v.meta_ref().is_empty() { return None; }
+    let (tag, key) = KEYS.with(|k| {
+        k.borrow().iter()
+            .find(|(tag, _)| v.meta_ref().contains_val(tag))
+            .map(|(tag, key)| (tag.clone(), key.clone()))
+    })?;
+    let k = key(v).unwrap_or_else(|| v.clone());
+    Some((tag, k))
+}
+
+// Evaluate body in an inner interpreter with the value on the stack,
+// and use the one value it leaves as the key.
+// It gives None if the body fails or is already running.
+fn key_fn(i: &Interpreter, body: Val) -> KeyFn {
+    let defs = i.defenv_ref().clone();
+    let inner = RefCell::new(i.new_inner_empty());
+    Rc::new(move |v: &Val| {
+        let mut inner = inner.try_borrow_mut().ok()?;
+        inner.reset();
+        *inner.stack_mut() = List::default();
+        *inner.defenv_mut() = defs.clone();
+        inner.stack_push(v.clone());
+        inner.eval_any_next(body.clone()).and_then(|()| inner.run()).ok()?;
+        if inner.stack_ref().len() != 1 { return None; }
+        inner.stack_mut().pop()
+    })
+}
+
+// Which kind of value it is, for ordering different kinds.
+fn kind(v: &Val) -> u8 {
+    if v.is::<bool>() { 0 }
+    else if number_type(v).is_some() { 1 }
+    else if v.is::<String>() { 2 }
+    else if v.is::<Symbol>() { 3 }
+    else if v.is::<Vec<u8>>() { 4 }
+    else if v.is::<List>() { 5 }
+    else if v.is::<Unique>() { 6 }
+    else if v.is::<TypeId>() { 7 }
+    else { 8 }
+}
+
+fn number_type(v: &Val) -> Option<u8> {
+    if v.is::<i64>() { Some(0) }
+    else if v.is::<BigInt>() { Some(1) }
+    else if v.is::<BigRational>() { Some(2) }
+    else if v.is::<f64>() { Some(3) }
+    else { None }
+}
+
+// Numbers as exact rationals if possible,
+// or else an infinite or NaN f64.
+enum Number { Exact(BigRational), Inexact(f64) }
+
+fn number(v: &Val) -> Number {
+    if let Some(n) = v.downcast_ref::<i64>() { Number::Exact(BigRational::from_integer((*n).into())) }
+    else if let Some(n) = v.downcast_ref::<BigInt>() { Number::Exact(BigRational::from_integer(n.clone())) }
+    else if let Some(r) = v.downcast_ref::<BigRational>() { Number::Exact(r.clone()) }
+    else {
+        let f = *v.downcast_ref::<f64>().unwrap();
+        match BigRational::from_float(f) {
+            Some(r) => Number::Exact(r),
+            None => Number::Inexact(f),
+        }
+    }
+}
+
+fn f64_compare(a: f64, b: f64) -> Ordering {
+    f64::from_bits(f64_bits(a)).total_cmp(&f64::from_bits(f64_bits(b)))
+}
+
+fn number_compare(a: &Val, b: &Val) -> Ordering {
+    let by_value = match (a.downcast_ref::<i64>(), b.downcast_ref::<i64>()) {
+        (Some(a), Some(b)) => return a.cmp(b),
+        _ => match (a.downcast_ref::<f64>(), b.downcast_ref::<f64>()) {
+            (Some(a), Some(b)) => return f64_compare(*a, *b),
+            _ => match (number(a), number(b)) {
+                (Number::Exact(a), Number::Exact(b)) => a.cmp(&b),
+                (Number::Inexact(a), Number::Inexact(b)) => f64_compare(a, b),
+                (Number::Exact(_), Number::Inexact(b)) => f64_compare(0.0, b),
+                (Number::Inexact(a), Number::Exact(_)) => f64_compare(a, 0.0),
+            },
+        },
+    };
+    by_value.then_with(|| number_type(a).cmp(&number_type(b)))
+}
+
+/// Compare two values structurally (see the [module docs](self)).
+pub fn value_compare(a: &Val, b: &Val) -> Ordering {
+    if a.ptr_eq(b) { return Ordering::Equal; }
+    match (keyed(a), keyed(b)) {
+        (None, None) => plain_compare(a, b),
+        (Some((ta, ka)), Some((tb, kb))) => ta.cmp(&tb).then_with(|| plain_compare(&ka, &kb)),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+// Compare without looking for a registered key first.
+fn plain_compare(a: &Val, b: &Val) -> Ordering {
+    let k = kind(a);
+    k.cmp(&kind(b)).then_with(|| match k {
+        0 => a.downcast_ref::<bool>().cmp(&b.downcast_ref::<bool>()),
+        1 => number_compare(a, b),
+        2 => a.downcast_ref::<String>().cmp(&b.downcast_ref::<String>()),
+        3 => {
+            let (a, b) = (a.downcast_ref::<Symbol>().unwrap(), b.downcast_ref::<Symbol>().unwrap());
+            if a == b { Ordering::Equal } else { a.as_ref().cmp(b.as_ref()) }
+        },
+        4 => a.downcast_ref::<Vec<u8>>().cmp(&b.downcast_ref::<Vec<u8>>()),
+        5 => {
+            let (a, b) = (a.downcast_ref::<List>().unwrap(), b.downcast_ref::<List>().unwrap());
+            a.iter().zip(b.iter())
+                .map(|(a, b)| value_compare(a, b))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len()))
+        },
+        6 => a.downcast_ref::<Unique>().cmp(&b.downcast_ref::<Unique>()),
+        7 => a.downcast_ref::<TypeId>().cmp(&b.downcast_ref::<TypeId>()),
+        _ => {
+            let ty = a.val_type_id();
+            ty.cmp(&b.val_type_id()).then_with(|| match hooks(ty) {
+                Some(h) => (h.compare)(a, b),
+                None => a.addr().cmp(&b.addr()),
+            })
+        },
+    })
+}
+
+/// Whether two values are structurally equal (see the [module docs](self)).
+pub fn value_equal(a: &Val, b: &Val) -> bool {
+    value_compare(a, b).is_eq()
+}
+
+/// Hash a value consistently with [value_equal].
+pub fn value_hash<H: Hasher>(v: &Val, state: &mut H) {
+    match keyed(v) {
+        Some((tag, k)) => {
+            state.write_u8(9);
+            tag.hash(state);
+            plain_hash(&k, state);
+        },
+        None => plain_hash(v, state),
+    }
+}
+
+// Hash without looking for a registered key first.
+fn plain_hash<H: Hasher>(v: &Val, state: &mut H) {
+    let k = kind(v);
+    state.write_u8(k);
+    if let Some(b) = v.downcast_ref::<bool>() { b.hash(state) }
+    else if let Some(n) = v.downcast_ref::<i64>() { n.hash(state) }
+    else if let Some(n) = v.downcast_ref::<BigInt>() { n.hash(state) }
+    else if let Some(r) = v.downcast_ref::<BigRational>() { r.hash(state) }
+    else if let Some(f) = v.downcast_ref::<f64>() { f64_bits(*f).hash(state) }
+    else if let Some(s) = v.downcast_ref::<String>() { s.hash(state) }
+    else if let Some(s) = v.downcast_ref::<Symbol>() { s.hash(state) }
+    else if let Some(b) = v.downcast_ref::<Vec<u8>>() { b.hash(state) }
+    else if let Some(l) = v.downcast_ref::<List>() {
+        state.write_usize(l.len());
+        for v in l.iter() { value_hash(v, state); }
+    }
+    else if let Some(u) = v.downcast_ref::<Unique>() { u.hash(state) }
+    else if let Some(t) = v.downcast_ref::<TypeId>() { t.hash(state) }
+    else {
+        let ty = v.val_type_id();
+        ty.hash(state);
+        match hooks(ty) {
+            Some(h) => (h.hash)(v, state),
+            None => v.addr().hash(state),
+        }
+    }
+}
+
+/// A [Val] that can be a key in a hash or ordered collection,
+/// using [value_compare] and [value_hash].
+#[derive(Clone)]
+pub struct ValueKey(pub Val);
+
+impl PartialEq for ValueKey {
+    fn eq(&self, other: &Self) -> bool { value_equal(&self.0, &other.0) }
+}
+impl Eq for ValueKey {}
+impl PartialOrd for ValueKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for ValueKey {
+    fn cmp(&self, other: &Self) -> Ordering { value_compare(&self.0, &other.0) }
+}
+impl Hash for ValueKey {
+    fn hash<H: Hasher>(&self, state: &mut H) { value_hash(&self.0, state) }
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    // a b value-equal -> bool
+    i.add_builtin("value-equal", |i: &mut Interpreter| {
+        let b = i.stack_pop_val()?;
+        let a = i.stack_pop_val()?;
+        i.stack_push(value_equal(&a, &b));
+        Ok(())
+    });
+    // a b value-compare -> -1 when a < b, 0 when equal, 1 when a > b
+    i.add_builtin("value-compare", |i: &mut Interpreter| {
+        let b = i.stack_pop_val()?;
+        let a = i.stack_pop_val()?;
+        i.stack_push(value_compare(&a, &b) as i64);
+        Ok(())
+    });
+    // v value-hash -> i64
+    i.add_builtin("value-hash", |i: &mut Interpreter| {
+        let v = i.stack_pop_val()?;
+        let mut hasher = DefaultHasher::new();
+        value_hash(&v, &mut hasher);
+        i.stack_push(hasher.finish() as i64);
+        Ok(())
+    });
+    // tag [ v -> key ] value-compare-key-register ->
+    // values with a meta entry for tag are compared and hashed by their key
+    i.add_builtin("value-compare-key-register", |i: &mut Interpreter| {
+        let body = i.stack_pop_val()?;
+        let tag = i.stack_pop::<Unique>()?.into_inner();
+        register_key(tag, key_fn(i, body));
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn list(v: Vec<Val>) -> Val { List::from(v).into() }
+
+    fn hash(v: &Val) -> u64 {
+        let mut h = DefaultHasher::new();
+        value_hash(v, &mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn deep() {
+        let a = list(vec![ 1.into(), list(vec![ "x".to_string().into() ]) ]);
+        let mut b = list(vec![ 1.into(), list(vec![ "x".to_string().into() ]) ]);
+        b.meta_mut().insert_val(UniqueGen::default().create(), true.into());
+        assert!(value_equal(&a, &b));
+        assert_eq!(hash(&a), hash(&b));
+        let c = list(vec![ 1.into(), list(vec![ "y".to_string().into() ]) ]);
+        assert_eq!(value_compare(&a, &c), Ordering::Less);
+        assert_eq!(value_compare(&list(vec![ 1.into() ]), &a), Ordering::Less);
+    }
+
+    #[test]
+    fn numbers() {
+        let v = |f: f64| Val::from(f);
+        assert!(!value_equal(&1.into(), &v(1.0)));
+        assert_eq!(value_compare(&1.into(), &v(1.5)), Ordering::Less);
+        assert_eq!(value_compare(&v(f64::NEG_INFINITY), &i64::MIN.into()), Ordering::Less);
+        assert_eq!(value_compare(&BigRational::new(1.into(), 3.into()).into(), &v(0.3)), Ordering::Greater);
+        assert!(value_equal(&v(0.0), &v(-0.0)));
+        assert!(value_equal(&v(f64::NAN), &v(-f64::NAN)));
+        assert_eq!(hash(&v(0.0)), hash(&v(-0.0)));
+        assert_eq!(value_compare(&true.into(), &0.into()), Ordering::Less);
+    }
+
+    #[test]
+    fn hooks() {
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+        struct Thing(i64);
+        impl Value for Thing {}
+        assert!(!value_equal(&Thing(1).into(), &Thing(1).into()));
+        register::<Thing>();
+        assert!(value_equal(&Thing(1).into(), &Thing(1).into()));
+        assert_eq!(hash(&Thing(1).into()), hash(&Thing(1).into()));
+        assert_eq!(value_compare(&Thing(1).into(), &Thing(2).into()), Ordering::Less);
+    }
+
+    #[test]
+    fn keys() {
+        let tag = UniqueGen::default().create();
+        let tagged = |v: Vec<Val>| {
+            let mut l = list(v);
+            l.meta_mut().insert_val(tag.clone(), true.into());
+            l
+        };
+        let mut i = Interpreter::new(vec![
+            tag.clone().into(),
+            list(vec![ "list-length".to_symbol().into() ]),
+            "value-compare-key-register".to_symbol().into(),
+        ]);
+        install(&mut i);
+        crate::builtins::list::install(&mut i);
+        assert!(i.run().is_ok());
+
+        // compared by length
+        let (a, b) = (tagged(vec![ 1.into(), 2.into() ]), tagged(vec![ 3.into(), 4.into() ]));
+        assert!(value_equal(&a, &b));
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(value_compare(&tagged(vec![ 5.into() ]), &a), Ordering::Less);
+        // but not equal to untagged things
+        let plain = list(vec![ 1.into(), 2.into() ]);
+        assert!(!value_equal(&a, &plain));
+        assert_eq!(value_compare(&plain, &a), Ordering::Less);
+        // including inside lists
+        assert!(value_equal(&list(vec![ a.clone() ]), &list(vec![ b ])));
+        assert!(!value_equal(&list(vec![ a ]), &list(vec![ plain ])));
+    }
+}
@@ -3,6 +3,7 @@
 
 use crate::base::*;
 use crate::interpreter::*;
+use crate::printer::Printer;
 use super::util;
 use std::any::TypeId;
 
@@ -12,7 +13,7 @@ pub fn quote(i: &mut Interpreter) -> BuiltinRet {
     if let Some(v) = v {
         i.stack_push(v);
     } else {
-        i.error("quote-nothing".to_symbol())?;
+        i.raise("quote-nothing", List::default())?;
     }
     Ok(())
 }
@@ -94,6 +95,16 @@ pub fn eval_if(i: &mut Interpreter) -> BuiltinRet {
     Ok(())
 }
 
+/// `eval-catch` - Evaluate the value on top of the stack,
+/// then put `false` on the stack, unless evaluating it raised an error,
+/// in which case the error is put on the stack instead.
+/// See [Interpreter::eval_next_catch].
+pub fn eval_catch(i: &mut Interpreter) -> BuiltinRet {
+    let e = i.stack_pop_val()?;
+    i.eval_next_catch(e)?;
+    Ok(())
+}
+
 /// `uplevel` - Evaluate the value on top of the stack as if in the parent stack frame.
 pub fn uplevel(i: &mut Interpreter) -> BuiltinRet {
     i.enter_parent_frame()?;
@@ -113,13 +124,24 @@ pub fn upquote(i: &mut Interpreter) -> BuiltinRet {
 /// This lets you eval anything without having to handle lists and symbols specially.
 pub fn value_to_constant(i: &mut Interpreter) -> BuiltinRet {
     let v = i.stack_pop_val()?;
-    i.stack_push(Builtin::from(move |i: &mut Interpreter| {
-        i.stack_push(v.clone());
-        Ok(())
-    }));
+    let c = i.make_constant(v);
+    i.stack_push(c);
     Ok(())
 }
 
+/// The names of the enabled features, as given by `features-enabled`.
+pub fn features_enabled() -> Vec<&'static str> {
+    vec![
+        #[cfg(feature = "enable_os")] "os",
+        #[cfg(feature = "enable_stdio")] "stdio",
+        #[cfg(feature = "enable_fs_os")] "fs-os",
+        #[cfg(feature = "enable_fs_embed")] "fs-embed",
+        #[cfg(feature = "enable_fs_zip")] "fs-zip",
+        #[cfg(feature = "enable_process")] "process",
+        #[cfg(feature = "wasm")] "wasm",
+    ]
+}
+
 /// Install all these functions.
 pub fn install(i: &mut Interpreter) {
     i.add_builtin("quote", quote);
@@ -129,6 +151,7 @@ pub fn install(i: &mut Interpreter) {
     i.add_builtin("bury", bury);
     i.add_builtin("eval", eval);
     i.add_builtin("eval-if", eval_if);
+    i.add_builtin("eval-catch", eval_catch);
     i.add_builtin("uplevel", uplevel);
     i.add_builtin("upquote", upquote);
     i.add_builtin("value->constant", value_to_constant);
@@ -178,7 +201,12 @@ pub fn install(i: &mut Interpreter) {
     i.add_builtin("bool-hash", util::value_hash::<bool>);
     util::add_const_type_builtin::<Symbol>(i, "<symbol>");
     i.add_builtin("symbol-equal", util::equality::<Symbol>);
-    i.add_builtin("symbol-hash", util::value_hash::<Symbol>);
+    // hash the name rather than the interned id, so it's stable between runs
+    i.add_builtin("symbol-hash", |i: &mut Interpreter| {
+        let s = i.stack_pop::<Symbol>()?;
+        i.stack_push(s.as_ref().to_string());
+        util::value_hash::<String>(i)
+    });
 
     util::add_const_type_builtin::<Unique>(i, "<unique>");
     i.add_builtin("unique-equal", util::equality::<Unique>);
@@ -205,6 +233,24 @@ pub fn install(i: &mut Interpreter) {
         i.stack_push(u);
         Ok(())
     });
+    // v value->readable -> string : print v on one line (see Printer)
+    // (value->string in ops.w uses this for anything but strings)
+    i.add_builtin("value->readable", |i: &mut Interpreter| {
+        let v = i.stack_pop_val()?;
+        let s = Printer::new(i.uniques_mut()).print(&v);
+        i.stack_push(s);
+        Ok(())
+    });
+    // v width value->readable-wrapped -> string :
+    // print v, putting list items on their own lines to fit in width columns
+    i.add_builtin("value->readable-wrapped", |i: &mut Interpreter| {
+        let width = i.stack_pop::<i64>()?.into_inner();
+        let v = i.stack_pop_val()?;
+        let mut p = Printer::new(i.uniques_mut());
+        p.set_width(Some(width.max(0) as usize));
+        i.stack_push(p.print(&v));
+        Ok(())
+    });
     i.add_builtin("unique-type-id?", |i: &mut Interpreter| {
         let is = i.stack_top::<Unique>()?.as_ref().is_type();
         i.stack_push(is);
@@ -243,15 +289,7 @@ pub fn install(i: &mut Interpreter) {
         Ok(())
     });
 
-    let enabled_features = List::from_iter(vec![
-        #[cfg(feature = "enable_os")] "os".to_symbol(),
-        #[cfg(feature = "enable_stdio")] "stdio".to_symbol(),
-        #[cfg(feature = "enable_fs_os")] "fs-os".to_symbol(),
-        #[cfg(feature = "enable_fs_embed")] "fs-embed".to_symbol(),
-        #[cfg(feature = "enable_fs_zip")] "fs-zip".to_symbol(),
-        #[cfg(feature = "enable_process")] "process".to_symbol(),
-        #[cfg(feature = "wasm")] "wasm".to_symbol(),
-    ]);
+    let enabled_features = List::from_iter(features_enabled().into_iter().map(Symbol::from));
     i.add_builtin("features-enabled", move |i: &mut Interpreter| {
         i.stack_push(enabled_features.clone());
         Ok(())
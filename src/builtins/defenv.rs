@@ -25,14 +25,14 @@ pub fn install(i: &mut Interpreter) {
         let def = i.stack_pop_val()?;
         let name = i.stack_pop::<Symbol>()?.into_inner();
         let mut defs = i.stack_pop::<DefEnv>()?;
-        defs.as_mut().insert_local(name.to_string(), def);
+        defs.as_mut().insert_local(name, def);
         i.stack_push(defs);
         Ok(())
     });
     i.add_builtin("defenv-lookup", |i: &mut Interpreter| {
         let name = i.stack_pop::<Symbol>()?.into_inner();
         let defs = i.stack_pop::<DefEnv>()?;
-        i.stack_push_option(defs.as_ref().lookup(name.as_ref()).cloned());
+        i.stack_push_option(defs.as_ref().lookup(&name).cloned());
         Ok(())
     });
 
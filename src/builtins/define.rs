@@ -14,7 +14,7 @@ pub fn install(i: &mut Interpreter) {
     });
     i.add_builtin("definition-resolve", |i: &mut Interpreter| {
         let name = i.stack_pop::<Symbol>()?.into_inner();
-        let res = i.resolve_definition(name.as_ref());
+        let res = i.resolve_definition(&name);
         i.stack_push_option(res.cloned());
         Ok(())
     });
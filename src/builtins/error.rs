@@ -0,0 +1,64 @@
+//! Making, raising and picking apart [WorstError] values
+
+use crate::base::*;
+use crate::error::WorstError;
+use crate::interpreter::*;
+use crate::builtins::util;
+
+/// value `error` -> : raise the value as an error.
+/// A [WorstError] (e.g. from [make_error]) is re-raised with its
+/// original backtrace; anything else becomes the payload of a new
+/// error of kind `error`.
+pub fn error(i: &mut Interpreter) -> BuiltinRet {
+    let v = i.stack_pop_val()?;
+    i.error(v)
+}
+
+/// kind payload `make-error` -> error : create an error value
+/// remembering the current call stack, without raising it.
+pub fn make_error(i: &mut Interpreter) -> BuiltinRet {
+    let payload = i.stack_pop_val()?;
+    let kind = i.stack_pop::<Symbol>()?.into_inner();
+    let e = i.make_error(kind, payload);
+    i.stack_push(e);
+    Ok(())
+}
+
+/// error `error-backtrace` -> list : a `(name span)` list for each frame
+/// the error was raised from, innermost first.
+/// Either may be false if unknown.
+pub fn error_backtrace(i: &mut Interpreter) -> BuiltinRet {
+    let e = i.stack_pop::<WorstError>()?;
+    let frames = e.as_ref().backtrace(i.uniques_mut()).map(|(name, span)| {
+        let name = name.cloned().map(Val::from).unwrap_or_else(|| false.into());
+        let span = span.cloned().map(Val::from).unwrap_or_else(|| false.into());
+        List::from(vec![name, span])
+    });
+    let frames = List::from_iter(frames);
+    i.stack_push(frames);
+    Ok(())
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    util::add_const_type_builtin::<WorstError>(i, "<error>");
+    i.add_builtin("error", error);
+    i.add_builtin("make-error", make_error);
+    i.add_builtin("error-kind", |i: &mut Interpreter| {
+        let e = i.stack_pop::<WorstError>()?;
+        i.stack_push(e.as_ref().kind().clone());
+        Ok(())
+    });
+    i.add_builtin("error-payload", |i: &mut Interpreter| {
+        let e = i.stack_pop::<WorstError>()?;
+        i.stack_push(e.as_ref().payload().clone());
+        Ok(())
+    });
+    i.add_builtin("error-source-span", |i: &mut Interpreter| {
+        let e = i.stack_pop::<WorstError>()?;
+        i.stack_push_option(e.as_ref().source().cloned());
+        Ok(())
+    });
+    i.add_builtin("error-backtrace", error_backtrace);
+}
+
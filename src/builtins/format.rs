@@ -0,0 +1,202 @@
+//! `format`: fill in a template string with values.
+//!
+//! Templates look like Rust's `format!`:
+//! - `{}` is the next value, `{2}` is the value at index 2
+//! - `{{` and `}}` are literal braces
+//! - after a `:`, `{:[[fill]align][width][.precision][?]}`
+//!   where align is `<` (left), `^` (centre) or `>` (right).
+//!   Numbers are right-aligned by default, and anything else is left-aligned.
+//!   Precision is digits after the point for an f64, or else the maximum width.
+//!   `?` prints strings with quotes and escapes.
+//!
+//! Strings are inserted as they are (unless `?`),
+//! and anything else is printed with the [Printer].
+//!
+//! Widths and precisions over [MAX_WIDTH] are format errors.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use crate::base::*;
+use crate::interpreter::*;
+use crate::printer::Printer;
+
+/// The largest width or precision allowed in a template.
+pub const MAX_WIDTH: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Align { Left, Centre, Right }
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<Align>,
+    width: Option<usize>,
+    precision: Option<usize>,
+    debug: bool,
+}
+
+fn parse_align(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Centre),
+        '>' => Some(Align::Right),
+        _ => None,
+    }
+}
+
+fn parse_spec(s: &str) -> Result<Spec, String> {
+    let mut spec = Spec::default();
+    let chars: Vec<char> = s.chars().collect();
+    let mut at = 0;
+    if let Some(a) = chars.get(1).copied().and_then(parse_align) {
+        spec.fill = Some(chars[0]);
+        spec.align = Some(a);
+        at = 2;
+    } else if let Some(a) = chars.first().copied().and_then(parse_align) {
+        spec.align = Some(a);
+        at = 1;
+    }
+    let digits = |at: &mut usize| -> Result<Option<usize>, String> {
+        let start = *at;
+        while chars.get(*at).is_some_and(char::is_ascii_digit) { *at += 1; }
+        if start == *at { return Ok(None); }
+        match chars[start..*at].iter().collect::<String>().parse() {
+            Ok(n) if n <= MAX_WIDTH => Ok(Some(n)),
+            _ => Err(format!("width over {MAX_WIDTH}: {s}")),
+        }
+    };
+    spec.width = digits(&mut at)?;
+    if chars.get(at) == Some(&'.') {
+        at += 1;
+        spec.precision = Some(digits(&mut at)?.ok_or("missing precision")?);
+    }
+    if chars.get(at) == Some(&'?') {
+        spec.debug = true;
+        at += 1;
+    }
+    if at != chars.len() {
+        return Err(format!("bad format spec: {s}"));
+    }
+    Ok(spec)
+}
+
+fn is_number(v: &Val) -> bool {
+    v.is::<i64>() || v.is::<f64>() || v.is::<BigInt>() || v.is::<BigRational>()
+}
+
+fn write_value(printer: &Printer, v: &Val, spec: &Spec, out: &mut String) {
+    let mut s = match (v.downcast_ref::<f64>(), v.downcast_ref::<String>()) {
+        (Some(f), _) if spec.precision.is_some() => {
+            let p = spec.precision.unwrap();
+            format!("{f:.p$}")
+        },
+        (_, Some(s)) if !spec.debug => s.clone(),
+        _ => printer.print(v),
+    };
+    if let (Some(p), false) = (spec.precision, v.is::<f64>()) {
+        if let Some((b, _)) = s.char_indices().nth(p) { s.truncate(b); }
+    }
+    let pad = spec.width.unwrap_or(0).saturating_sub(s.chars().count());
+    let align = spec.align.unwrap_or(if is_number(v) { Align::Right } else { Align::Left });
+    let (before, after) = match align {
+        Align::Left => (0, pad),
+        Align::Centre => (pad / 2, pad - pad / 2),
+        Align::Right => (pad, 0),
+    };
+    let fill = spec.fill.unwrap_or(' ');
+    out.extend(std::iter::repeat_n(fill, before));
+    out.push_str(&s);
+    out.extend(std::iter::repeat_n(fill, after));
+}
+
+/// Fill in `template` with `args` (see the [module docs](self)),
+/// or give a message saying what's wrong with the template.
+pub fn format(printer: &Printer, template: &str, args: &[Val]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut next = 0;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('{') if field.is_empty() => { out.push('{'); break; },
+                        Some('}') => {
+                            let (pos, spec) = field.split_once(':').unwrap_or((&field, ""));
+                            let idx = if pos.is_empty() {
+                                next += 1;
+                                next - 1
+                            } else {
+                                pos.parse::<usize>().map_err(|_| format!("bad position: {pos}"))?
+                            };
+                            let v = args.get(idx).ok_or_else(|| format!("no value at {idx}"))?;
+                            write_value(printer, v, &parse_spec(spec)?, &mut out);
+                            break;
+                        },
+                        Some(c) => field.push(c),
+                        None => return Err("unclosed {".into()),
+                    }
+                }
+            },
+            '}' => {
+                if chars.next() != Some('}') { return Err("unmatched }".into()); }
+                out.push('}');
+            },
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    // template list format -> string
+    i.add_builtin("format", |i: &mut Interpreter| {
+        let args = i.stack_pop::<List>()?.into_inner();
+        let template = i.stack_pop::<String>()?.into_inner();
+        let args = args.iter().cloned().collect::<Vec<_>>();
+        let printer = Printer::new(i.uniques_mut());
+        match format(&printer, &template, &args) {
+            Ok(s) => i.stack_push(s),
+            Err(e) => return Err(i.make_error("format-error", List::from(vec![
+                e.into(), template.into(),
+            ]))),
+        }
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fmt(template: &str, args: Vec<Val>) -> Result<String, String> {
+        format(&Printer::default(), template, &args)
+    }
+
+    #[test]
+    fn positions() {
+        let args = vec![ "a".to_string().into(), 2.into() ];
+        assert_eq!(fmt("{} {} {0} {1:?}", args.clone()).unwrap(), "a 2 a 2");
+        assert_eq!(fmt("{0:?} {{}}", args.clone()).unwrap(), "\"a\" {}");
+        assert!(fmt("{2}", args.clone()).is_err());
+        assert!(fmt("{", args.clone()).is_err());
+        assert!(fmt("}", args).is_err());
+    }
+
+    #[test]
+    fn specs() {
+        assert_eq!(fmt("[{:5}]", vec![ 42.into() ]).unwrap(), "[   42]");
+        assert_eq!(fmt("[{:5}]", vec![ "ab".to_string().into() ]).unwrap(), "[ab   ]");
+        assert_eq!(fmt("[{:*^6}]", vec![ "ab".to_string().into() ]).unwrap(), "[**ab**]");
+        assert_eq!(fmt("[{:<6.2}]", vec![ 1.23456.into() ]).unwrap(), "[1.23  ]");
+        assert_eq!(fmt("[{:.3}]", vec![ "abcdef".to_string().into() ]).unwrap(), "[abc]");
+        let l = List::from(vec![ 1.into(), "x".to_string().into() ]);
+        assert_eq!(fmt("{}", vec![ l.into() ]).unwrap(), "(1 \"x\")");
+        assert!(fmt("{:x}", vec![ 1.into() ]).is_err());
+        assert!(fmt("{:99999999999}", vec![ 1.into() ]).is_err());
+        assert!(fmt("{:.99999999999}", vec![ 1.5.into() ]).is_err());
+        assert!(fmt("{:99999999999999999999999}", vec![ 1.into() ]).is_err());
+    }
+}
@@ -3,13 +3,17 @@
 //! courtesy of [include_dir].
 
 use std::io;
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 use crate::base::*;
+use crate::compiled;
 use crate::interpreter::*;
+use crate::reader::{ Reader, ReadError };
 use crate::builtins::util;
 use include_dir::{include_dir, Dir};
 
 static EMBED_FS: Dir = include_dir!("$CARGO_MANIFEST_DIR/lib");
+// lib/*.w compiled by build.rs into *.wc
+static COMPILED_FS: Dir = include_dir!("$OUT_DIR/compiled");
 
 /// An open reference to a file found in the embedded filesystem.
 #[derive(Clone)]
@@ -29,6 +33,22 @@ pub fn open_read_str(path: impl AsRef<std::path::Path>) -> Option<&'static str>
     EMBED_FS.get_file(path)?.contents_utf8()
 }
 
+/// Read the code in the given file, if it exists,
+/// with the path as the source name.
+/// Uses the [compiled] version made at build time if there is one.
+pub fn read_code(path: impl AsRef<Path>, uniques: &mut UniqueGen) -> Option<Result<List, ReadError>> {
+    let path = path.as_ref();
+    let compiled = COMPILED_FS.get_file(path.with_extension("wc"))
+        .and_then(|f| compiled::decode(f.contents(), uniques).ok());
+    if let Some(code) = compiled {
+        return Some(Ok(code));
+    }
+    let src = open_read_str(path)?;
+    let mut reader = Reader::new(uniques);
+    reader.set_source_name(path.to_string_lossy());
+    Some(reader.read_all(&mut src.chars()).map(List::from))
+}
+
 impl io::Read for File {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.handle.read(buf)
@@ -46,6 +66,16 @@ pub fn install(i: &mut Interpreter) {
         }
         Ok(())
     });
+    // fs-path embedded-file-read->list -> list, or #f if it doesn't exist
+    i.add_builtin("embedded-file-read->list", |i: &mut Interpreter| {
+        let path = i.stack_pop::<PathBuf>()?;
+        match read_code(path.as_ref(), i.uniques_mut()) {
+            Some(Ok(code)) => i.stack_push(code),
+            Some(Err(e)) => i.stack_push_error(format!("{:?}", e)),
+            None => i.stack_push_error(false),
+        }
+        Ok(())
+    });
     util::add_const_type_builtin::<File>(i, "<embedded-file-port>");
     i.add_builtin("embedded-file-port->string", util::port_to_string::<File>);
     i.add_builtin("embedded-file-port-read-range", util::port_read_range::<File>);
@@ -0,0 +1,166 @@
+//! Persistent hash maps ([HashMap]) and sets ([HashSet]).
+//!
+//! Keys can be any value, and are found using `value-equal` and `value-hash`
+//! (see [compare](super::compare)), so meta is ignored.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasherDefault;
+use im_rc::{HashMap as ImHashMap, HashSet as ImHashSet};
+use crate::base::*;
+use crate::builtins::util;
+use crate::interpreter::*;
+use super::compare::ValueKey;
+
+// Not randomly seeded, so iteration order is the same every time.
+type FixedState = BuildHasherDefault<DefaultHasher>;
+
+/// Values by key.
+#[derive(Default, Clone)]
+pub(crate) struct HashMap {
+    pub(crate) data: ImHashMap<ValueKey, Val, FixedState>,
+}
+impl Value for HashMap {}
+
+/// Distinct values.
+#[derive(Default, Clone)]
+pub(crate) struct HashSet {
+    pub(crate) data: ImHashSet<ValueKey, FixedState>,
+}
+impl Value for HashSet {}
+
+fn pop_key(i: &mut Interpreter) -> BuiltinRet<ValueKey> {
+    Ok(ValueKey(i.stack_pop_val()?))
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    util::add_const_type_builtin::<HashMap>(i, "<hashmap>");
+    i.add_builtin("make-hashmap", util::make_default::<HashMap>);
+    // hashmap key value hashmap-insert -> hashmap
+    i.add_builtin("hashmap-insert", |i: &mut Interpreter| {
+        let v = i.stack_pop_val()?;
+        let k = pop_key(i)?;
+        let mut map = i.stack_pop::<HashMap>()?;
+        map.as_mut().data.insert(k, v);
+        i.stack_push(map);
+        Ok(())
+    });
+    // hashmap key hashmap-get -> value, or false
+    i.add_builtin("hashmap-get", |i: &mut Interpreter| {
+        let k = pop_key(i)?;
+        let map = i.stack_pop::<HashMap>()?;
+        i.stack_push_option(map.as_ref().data.get(&k).cloned());
+        Ok(())
+    });
+    i.add_builtin("hashmap-contains", |i: &mut Interpreter| {
+        let k = pop_key(i)?;
+        let map = i.stack_pop::<HashMap>()?;
+        i.stack_push(map.as_ref().data.contains_key(&k));
+        Ok(())
+    });
+    // hashmap key hashmap-remove -> hashmap
+    i.add_builtin("hashmap-remove", |i: &mut Interpreter| {
+        let k = pop_key(i)?;
+        let mut map = i.stack_pop::<HashMap>()?;
+        map.as_mut().data.remove(&k);
+        i.stack_push(map);
+        Ok(())
+    });
+    i.add_builtin("hashmap-keys", |i: &mut Interpreter| {
+        let map = i.stack_pop::<HashMap>()?;
+        i.stack_push(List::from(map.as_ref().data.keys().map(|k| k.0.clone()).collect::<Vec<_>>()));
+        Ok(())
+    });
+    i.add_builtin("hashmap-values", |i: &mut Interpreter| {
+        let map = i.stack_pop::<HashMap>()?;
+        i.stack_push(List::from(map.as_ref().data.values().cloned().collect::<Vec<_>>()));
+        Ok(())
+    });
+    // a b hashmap-merge -> hashmap : everything in a and b, preferring b
+    i.add_builtin("hashmap-merge", |i: &mut Interpreter| {
+        let b = i.stack_pop::<HashMap>()?.into_inner();
+        let a = i.stack_pop::<HashMap>()?.into_inner();
+        i.stack_push(HashMap { data: b.data.union(a.data) });
+        Ok(())
+    });
+    i.add_builtin("hashmap-length", |i: &mut Interpreter| {
+        let map = i.stack_pop::<HashMap>()?;
+        i.stack_push(map.as_ref().data.len() as i64);
+        Ok(())
+    });
+    i.add_builtin("hashmap-empty?", |i: &mut Interpreter| {
+        let map = i.stack_pop::<HashMap>()?;
+        i.stack_push(map.as_ref().data.is_empty());
+        Ok(())
+    });
+
+    util::add_const_type_builtin::<HashSet>(i, "<hashset>");
+    i.add_builtin("make-hashset", util::make_default::<HashSet>);
+    // hashset value hashset-insert -> hashset
+    i.add_builtin("hashset-insert", |i: &mut Interpreter| {
+        let v = pop_key(i)?;
+        let mut set = i.stack_pop::<HashSet>()?;
+        set.as_mut().data.insert(v);
+        i.stack_push(set);
+        Ok(())
+    });
+    i.add_builtin("hashset-contains", |i: &mut Interpreter| {
+        let k = pop_key(i)?;
+        let set = i.stack_pop::<HashSet>()?;
+        i.stack_push(set.as_ref().data.contains(&k));
+        Ok(())
+    });
+    i.add_builtin("hashset-remove", |i: &mut Interpreter| {
+        let k = pop_key(i)?;
+        let mut set = i.stack_pop::<HashSet>()?;
+        set.as_mut().data.remove(&k);
+        i.stack_push(set);
+        Ok(())
+    });
+    i.add_builtin("hashset-values", |i: &mut Interpreter| {
+        let set = i.stack_pop::<HashSet>()?;
+        i.stack_push(List::from(set.as_ref().data.iter().map(|v| v.0.clone()).collect::<Vec<_>>()));
+        Ok(())
+    });
+    // a b hashset-merge -> hashset : everything in a or b
+    i.add_builtin("hashset-merge", |i: &mut Interpreter| {
+        let b = i.stack_pop::<HashSet>()?.into_inner();
+        let a = i.stack_pop::<HashSet>()?.into_inner();
+        i.stack_push(HashSet { data: b.data.union(a.data) });
+        Ok(())
+    });
+    i.add_builtin("hashset-length", |i: &mut Interpreter| {
+        let set = i.stack_pop::<HashSet>()?;
+        i.stack_push(set.as_ref().data.len() as i64);
+        Ok(())
+    });
+    i.add_builtin("hashset-empty?", |i: &mut Interpreter| {
+        let set = i.stack_pop::<HashSet>()?;
+        i.stack_push(set.as_ref().data.is_empty());
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_get_remove() {
+        let sym = |s: &str| Val::from(s.to_symbol());
+        let mut i = Interpreter::new(vec![
+            sym("make-hashmap"),
+            "a".to_string().into(), 1.into(), sym("hashmap-insert"),
+            "a".to_string().into(), 2.into(), sym("hashmap-insert"),
+            "b".to_string().into(), 3.into(), sym("hashmap-insert"),
+            sym("clone"), "a".to_string().into(), sym("hashmap-get"),
+            sym("swap"), "b".to_string().into(), sym("hashmap-remove"),
+            sym("hashmap-length"),
+        ]);
+        install(&mut i);
+        crate::builtins::core::install(&mut i);
+        assert!(i.run().is_ok());
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 1);
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 2);
+    }
+}
@@ -7,8 +7,8 @@ use crate::interpreter::*;
 use im_rc::OrdMap;
 
 #[derive(Default, Clone)]
-struct I64Map {
-    data: OrdMap<i64, Val>,
+pub(crate) struct I64Map {
+    pub(crate) data: OrdMap<i64, Val>,
 }
 impl Value for I64Map {}
 
@@ -10,9 +10,20 @@ use crate::builtins::util;
 
 // TODO no wrapper, just use Interpreter directly and wrap in a place in worst
 #[derive(Clone, Default)]
-struct Interp(Rc<RefCell<Interpreter>>);
+pub(crate) struct Interp(Rc<RefCell<Interpreter>>);
 impl Value for Interp {}
 
+// n|#f, where #f means no limit
+fn pop_limit(i: &mut Interpreter) -> BuiltinRet<Option<usize>> {
+    let v = i.stack_pop_val()?;
+    if v.downcast_ref::<bool>() == Some(&false) {
+        return Ok(None);
+    }
+    i.stack_push(v);
+    let n = i.stack_pop::<i64>()?.into_inner();
+    Ok(Some(n.max(0) as usize))
+}
+
 /// Install all the interpreter functions.
 pub fn install(i: &mut Interpreter) {
     util::add_const_type_builtin::<Interp>(i, "<interpreter>");
@@ -30,6 +41,77 @@ pub fn install(i: &mut Interpreter) {
         }
         Ok(())
     });
+    // interp steps interpreter-run-steps -> interp #t|paused-value #t
+    //                                    or interp #f
+    // #f means it ran out of steps and can be run again to continue,
+    // otherwise it stopped and gives what interpreter-run would, then #t
+    i.add_builtin("interpreter-run-steps",  |i: &mut Interpreter| {
+        let steps = i.stack_pop::<i64>()?.into_inner();
+        let interp = i.stack_top::<Interp>()?;
+        let r = interp.as_ref().0.borrow_mut().run_with_fuel(steps.max(0) as usize);
+        match r {
+            Ok(true) => i.stack_push(true),
+            Ok(false) => {
+                i.stack_push(false);
+                return Ok(());
+            },
+            Err(e) => i.stack_push(e),
+        }
+        i.stack_push(true);
+        Ok(())
+    });
+    // interp interpreter-snapshot -> interp bytevector|error
+    i.add_builtin("interpreter-snapshot",  |i: &mut Interpreter| {
+        let interp = i.stack_top::<Interp>()?;
+        let r = interp.as_ref().0.borrow().snapshot();
+        i.stack_push_result(r.map_err(|e| e.to_string()));
+        Ok(())
+    });
+    // interp bytevector interpreter-restore-snapshot -> interp #t|error
+    // builtins are looked up by name in a fresh set of all builtins
+    i.add_builtin("interpreter-restore-snapshot",  |i: &mut Interpreter| {
+        let data = i.stack_pop::<Vec<u8>>()?;
+        let interp = i.stack_top::<Interp>()?;
+        let names = crate::builtins::all_defenv(i);
+        let r = interp.as_ref().0.borrow_mut().restore_snapshot(data.as_ref(), &names);
+        i.stack_push_result(r.map(|()| true).map_err(|e| e.to_string()));
+        Ok(())
+    });
+    // interp n|#f interpreter-max-frame-depth-set -> interp
+    i.add_builtin("interpreter-max-frame-depth-set",  |i: &mut Interpreter| {
+        let max = pop_limit(i)?;
+        let interp = i.stack_top::<Interp>()?;
+        interp.as_ref().0.borrow_mut().set_max_frame_depth(max);
+        Ok(())
+    });
+    // interp n|#f interpreter-max-stack-length-set -> interp
+    i.add_builtin("interpreter-max-stack-length-set",  |i: &mut Interpreter| {
+        let max = pop_limit(i)?;
+        let interp = i.stack_top::<Interp>()?;
+        interp.as_ref().0.borrow_mut().set_max_stack_length(max);
+        Ok(())
+    });
+    // the same for the current interpreter
+    i.add_builtin("max-frame-depth", |i: &mut Interpreter| {
+        let max = i.max_frame_depth().map(|n| n as i64);
+        i.stack_push_option(max);
+        Ok(())
+    });
+    i.add_builtin("max-frame-depth-set", |i: &mut Interpreter| {
+        let max = pop_limit(i)?;
+        i.set_max_frame_depth(max);
+        Ok(())
+    });
+    i.add_builtin("max-stack-length", |i: &mut Interpreter| {
+        let max = i.max_stack_length().map(|n| n as i64);
+        i.stack_push_option(max);
+        Ok(())
+    });
+    i.add_builtin("max-stack-length-set", |i: &mut Interpreter| {
+        let max = pop_limit(i)?;
+        i.set_max_stack_length(max);
+        Ok(())
+    });
     i.add_builtin("interpreter-complete?",  |i: &mut Interpreter| {
         let interp = i.stack_top::<Interp>()?;
         i.stack_push(interp.as_ref().0.borrow().is_complete());
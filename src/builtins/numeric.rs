@@ -1,9 +1,15 @@
 
 //! Numeric operations that work on i64 and f64 numbers
+//!
+//! i64 operations that would overflow give a [BigInt] instead
+//! (see [bignum](super::bignum)).
 
 // Thanks to Racket docs for guidance
 // https://docs.racket-lang.org/reference/generic-numbers.html
 
+use num_bigint::BigInt;
+use num_traits::Signed;
+use super::bignum::integer_val;
 use super::util;
 use crate::base::*;
 use crate::interpreter::*;
@@ -44,6 +50,45 @@ fn unop<T: Value + Clone, R: Value>(f: impl Fn(T) -> R) -> impl Fn(&mut Interpre
     }
 }
 
+/// An i64 operation that gives a [BigInt] (via `big`) when `checked` overflows.
+fn checked_binop(checked: impl Fn(i64, i64) -> Option<i64>,
+                 big: impl Fn(BigInt, BigInt) -> BigInt) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let b = i.stack_pop::<i64>()?.into_inner();
+        let a = i.stack_pop::<i64>()?.into_inner();
+        match checked(a, b) {
+            Some(r) => i.stack_push(r),
+            None => i.stack_push(integer_val(big(a.into(), b.into()))),
+        }
+        Ok(())
+    }
+}
+
+fn checked_unop(checked: impl Fn(i64) -> Option<i64>,
+                big: impl Fn(BigInt) -> BigInt) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let a = i.stack_pop::<i64>()?.into_inner();
+        match checked(a) {
+            Some(r) => i.stack_push(r),
+            None => i.stack_push(integer_val(big(a.into()))),
+        }
+        Ok(())
+    }
+}
+
+/// The bits of an [f64], but the same for `0.0` and `-0.0`, and for all NaNs.
+pub fn f64_bits(a: f64) -> u64 {
+    if a == 0.0 { 0 } else if a.is_nan() { f64::NAN.to_bits() } else { a.to_bits() }
+}
+
+/// Hash an [f64] by its bits, so that it agrees with `f64-equal`:
+/// `0.0` and `-0.0` hash the same, as do all NaNs (though NaN isn't equal to itself).
+pub fn f64_hash(i: &mut Interpreter) -> BuiltinRet {
+    let a = i.stack_pop::<f64>()?.into_inner();
+    i.stack_push(f64_bits(a) as i64);
+    util::value_hash::<i64>(i)
+}
+
 /// All binary operations are the same order as infix, so `a b op` infix is `a op b`.
 fn binop<T: Value + Clone, R: Value>(f: impl Fn(T, T) -> R) -> impl Fn(&mut Interpreter) -> BuiltinRet {
     move |i| {
@@ -61,6 +106,20 @@ pub fn install(i: &mut Interpreter) {
 
     i.add_builtin("i64->string", util::value_tostring_debug::<i64>);
     i.add_builtin("f64->string", util::value_tostring_debug::<f64>);
+    // f precision f64->string-fixed -> string : with precision digits after the point
+    i.add_builtin("f64->string-fixed", |i: &mut Interpreter| {
+        let p = i.stack_pop::<i64>()?.into_inner().max(0) as usize;
+        let a = i.stack_pop::<f64>()?.into_inner();
+        i.stack_push(format!("{a:.p$}"));
+        Ok(())
+    });
+    // f precision f64->string-scientific -> string : like 1.50e3 for precision 2
+    i.add_builtin("f64->string-scientific", |i: &mut Interpreter| {
+        let p = i.stack_pop::<i64>()?.into_inner().max(0) as usize;
+        let a = i.stack_pop::<f64>()?.into_inner();
+        i.stack_push(format!("{a:.p$e}"));
+        Ok(())
+    });
 
     i.add_builtin("i64->f64", i64_to_f64);
     i.add_builtin("f64->i64", f64_to_i64);
@@ -69,28 +128,74 @@ pub fn install(i: &mut Interpreter) {
     i.add_builtin("f64-equal", util::equality::<f64>);
     i.add_builtin("i64-compare", util::comparison::<i64>);
     i.add_builtin("f64-compare", util::comparison::<f64>);
+    // like f64-compare but never an error:
+    // -NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN
+    i.add_builtin("f64-total-compare", binop::<f64, i64>(|a, b| a.total_cmp(&b) as i64));
     i.add_builtin("i64-hash", util::value_hash::<i64>);
-    // f64 not hashable
-    // i.add_builtin("f64-hash", util::value_hash::<f64>);
+    i.add_builtin("f64-hash", f64_hash);
 
-    i.add_builtin("i64-add", binop::<i64, i64>(|a, b| a + b));
+    i.add_builtin("i64-add", checked_binop(i64::checked_add, |a, b| a + b));
     i.add_builtin("f64-add", binop::<f64, f64>(|a, b| a + b));
-    i.add_builtin("i64-sub", binop::<i64, i64>(|a, b| a - b));
+    i.add_builtin("i64-sub", checked_binop(i64::checked_sub, |a, b| a - b));
     i.add_builtin("f64-sub", binop::<f64, f64>(|a, b| a - b));
-    i.add_builtin("i64-mul", binop::<i64, i64>(|a, b| a * b));
+    i.add_builtin("i64-mul", checked_binop(i64::checked_mul, |a, b| a * b));
     i.add_builtin("f64-mul", binop::<f64, f64>(|a, b| a * b));
-    i.add_builtin("i64-div", div_nozero::<i64>);
+    i.add_builtin("i64-div", |i: &mut Interpreter| {
+        // MIN / -1 overflows
+        let b = i.stack_top::<i64>()?.into_inner();
+        if b == 0 { div_nozero::<i64>(i) } else { checked_binop(i64::checked_div, |a, b| a / b)(i) }
+    });
     i.add_builtin("f64-div", binop::<f64, f64>(|a, b| a / b));
 
-    i.add_builtin("i64-negate", unop::<i64, i64>(|a| -a));
+    i.add_builtin("i64-negate", checked_unop(i64::checked_neg, |a| -a));
     i.add_builtin("f64-negate", unop::<f64, f64>(|a| -a));
 
-    i.add_builtin("i64-remainder", binop::<i64, i64>(|a, b| a % b));
+    i.add_builtin("i64-remainder", |i: &mut Interpreter| {
+        let b = i.stack_pop::<i64>()?.into_inner();
+        let a = i.stack_pop::<i64>()?.into_inner();
+        if b == 0 {
+            i.stack_push_error(false);
+        } else {
+            // MIN % -1 overflows, but it's 0 anyway
+            i.stack_push(a.checked_rem(b).unwrap_or(0));
+        }
+        Ok(())
+    });
 
-    i.add_builtin("i64-abs", unop::<i64, i64>(|a| a.abs()));
+    i.add_builtin("i64-abs", checked_unop(i64::checked_abs, |a| a.abs()));
     i.add_builtin("f64-abs", unop::<f64, f64>(|a| a.abs()));
 
+    i.add_builtin("f64-min", binop::<f64, f64>(|a, b| a.min(b)));
+    i.add_builtin("f64-max", binop::<f64, f64>(|a, b| a.max(b)));
+    i.add_builtin("f64-signum", unop::<f64, f64>(|a| a.signum()));
+    i.add_builtin("f64-copysign", binop::<f64, f64>(|a, b| a.copysign(b)));
+
+    i.add_builtin("f64-nan?", unop::<f64, bool>(|a| a.is_nan()));
+    i.add_builtin("f64-infinite?", unop::<f64, bool>(|a| a.is_infinite()));
+    i.add_builtin("f64-finite?", unop::<f64, bool>(|a| a.is_finite()));
+
+    i.add_builtin("f64-floor", unop::<f64, f64>(|a| a.floor()));
+    i.add_builtin("f64-ceil", unop::<f64, f64>(|a| a.ceil()));
+    // halfway rounds away from zero
+    i.add_builtin("f64-round", unop::<f64, f64>(|a| a.round()));
+    i.add_builtin("f64-truncate", unop::<f64, f64>(|a| a.trunc()));
+    i.add_builtin("f64-fract", unop::<f64, f64>(|a| a.fract()));
+
     i.add_builtin("f64-sqrt", unop::<f64, f64>(|a| a.sqrt()));
+    i.add_builtin("f64-cbrt", unop::<f64, f64>(|a| a.cbrt()));
+    i.add_builtin("f64-hypot", binop::<f64, f64>(|a, b| a.hypot(b)));
+    // a b f64-pow -> a^b
+    i.add_builtin("f64-pow", binop::<f64, f64>(|a, b| a.powf(b)));
+    i.add_builtin("f64-exp", unop::<f64, f64>(|a| a.exp()));
+    i.add_builtin("f64-exp2", unop::<f64, f64>(|a| a.exp2()));
+    i.add_builtin("f64-ln", unop::<f64, f64>(|a| a.ln()));
+    i.add_builtin("f64-log2", unop::<f64, f64>(|a| a.log2()));
+    i.add_builtin("f64-log10", unop::<f64, f64>(|a| a.log10()));
+    // a base f64-log -> log of a in base
+    i.add_builtin("f64-log", binop::<f64, f64>(|a, b| a.log(b)));
+
+    i.add_definition("f64-pi", std::f64::consts::PI);
+    i.add_definition("f64-e", std::f64::consts::E);
 
     i.add_builtin("f64-sin", unop::<f64, f64>(|a| a.sin()));
     i.add_builtin("f64-cos", unop::<f64, f64>(|a| a.cos()));
@@ -98,6 +203,11 @@ pub fn install(i: &mut Interpreter) {
     i.add_builtin("f64-asin", unop::<f64, f64>(|a| a.asin()));
     i.add_builtin("f64-acos", unop::<f64, f64>(|a| a.acos()));
     i.add_builtin("f64-atan", unop::<f64, f64>(|a| a.atan()));
+    // y x f64-atan2 -> angle of (x, y)
+    i.add_builtin("f64-atan2", binop::<f64, f64>(|a, b| a.atan2(b)));
+    i.add_builtin("f64-sinh", unop::<f64, f64>(|a| a.sinh()));
+    i.add_builtin("f64-cosh", unop::<f64, f64>(|a| a.cosh()));
+    i.add_builtin("f64-tanh", unop::<f64, f64>(|a| a.tanh()));
 
     i.add_builtin("i64-bitand", binop::<i64, i64>(|a, b| a & b));
     i.add_builtin("i64-bitxor", binop::<i64, i64>(|a, b| a ^ b));
@@ -109,3 +219,25 @@ pub fn install(i: &mut Interpreter) {
     }));
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f64_hash_agrees_with_equal() {
+        let mut i = Interpreter::new(vec![
+            0.0.into(), "f64-hash".to_symbol().into(),
+            (-0.0).into(), "f64-hash".to_symbol().into(),
+            f64::NAN.into(), "f64-nan?".to_symbol().into(),
+            f64::NAN.into(), 1.0.into(), "f64-total-compare".to_symbol().into(),
+        ]);
+        install(&mut i);
+        assert!(i.run().is_ok());
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 1);
+        assert!(i.stack_pop::<bool>().ok().unwrap().into_inner());
+        let a = i.stack_pop::<i64>().ok().unwrap().into_inner();
+        let b = i.stack_pop::<i64>().ok().unwrap().into_inner();
+        assert_eq!(a, b);
+    }
+}
@@ -1,9 +1,26 @@
 
 //! Querying and interacting with the ambient operating system
 
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
 use crate::base::*;
 use crate::interpreter::*;
 
+/// What [Interpreter::run] gives back after `exit`,
+/// with the requested exit status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exit(pub i64);
+impl Value for Exit {}
+
+impl Exit {
+    /// The status to give the operating system: the requested one if it's
+    /// from 0 to 255, or else 1 so that e.g. -1 is still a failure.
+    pub fn code(&self) -> u8 {
+        u8::try_from(self.0).unwrap_or(1)
+    }
+}
+
 /// `command-line-arguments` -> list :
 /// the command-line arguments from program invocation.
 pub fn command_line_arguments(i: &mut Interpreter) -> BuiltinRet {
@@ -23,5 +40,71 @@ pub fn environment_variable(i: &mut Interpreter) -> BuiltinRet {
 pub fn install(i: &mut Interpreter) {
     i.add_builtin("command-line-arguments", command_line_arguments);
     i.add_builtin("environment-variable", environment_variable);
+
+    let hooks: Rc<RefCell<Vec<Val>>> = Rc::default();
+    // body exit-hook-add : evaluate body on exit, most recently added first
+    let add_hooks = hooks.clone();
+    i.add_builtin("exit-hook-add", move |i: &mut Interpreter| {
+        let body = i.stack_pop_val()?;
+        add_hooks.borrow_mut().push(body);
+        Ok(())
+    });
+    // status exit : run the exit hooks, flush stdout and stderr,
+    // then stop running with an Exit (which can't be caught)
+    i.add_builtin("exit", move |i: &mut Interpreter| {
+        let status = i.stack_pop::<i64>()?.into_inner();
+        i.eval_next_once(move |_i: &mut Interpreter| {
+            let _ = std::io::stdout().flush();
+            let _ = std::io::stderr().flush();
+            Err(Exit(status).into())
+        });
+        // taken so they only run once, even if one of them exits
+        for hook in hooks.take() {
+            i.eval_any_next(hook)?;
+        }
+        Ok(())
+    });
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exit_hooks() {
+        let hook = |n: i64| -> Val {
+            List::from(vec![ n.into(), "log".to_symbol().into() ]).into()
+        };
+        let mut i = Interpreter::new(vec![
+            hook(1), "exit-hook-add".to_symbol().into(),
+            hook(2), "exit-hook-add".to_symbol().into(),
+            List::from(vec![ 3.into(), "exit".to_symbol().into() ]).into(),
+            "catch".to_symbol().into(),
+            "unreachable".to_symbol().into(),
+        ]);
+        install(&mut i);
+        let log: Rc<RefCell<Vec<i64>>> = Rc::default();
+        let l = log.clone();
+        i.add_builtin("log", move |i: &mut Interpreter| {
+            l.borrow_mut().push(i.stack_pop::<i64>()?.into_inner());
+            Ok(())
+        });
+        i.add_builtin("catch", |i: &mut Interpreter| {
+            let body = i.stack_pop_val()?;
+            i.eval_next_catch(body)
+        });
+        let e = i.run().expect_err("exited");
+        assert_eq!(e.downcast_ref::<Exit>(), Some(&Exit(3)));
+        assert_eq!(*log.borrow(), vec![2, 1]);
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(Exit(0).code(), 0);
+        assert_eq!(Exit(255).code(), 255);
+        assert_eq!(Exit(-1).code(), 1);
+        assert_eq!(Exit(i64::MIN).code(), 1);
+        assert_eq!(Exit(256).code(), 1);
+    }
+}
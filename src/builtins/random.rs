@@ -0,0 +1,202 @@
+//! A seedable pseudo-random number generator ([Rng]).
+//!
+//! It's a value like any other, so builtins that use it take an [Rng]
+//! and give back the advanced one along with their result:
+//! the same seed always gives the same numbers.
+//! There's no OS dependency unless `enable_os` is on for `make-rng-os`.
+
+use crate::base::*;
+use crate::builtins::util;
+use crate::interpreter::*;
+
+/// xoshiro256** (see <https://prng.di.unimi.it/>).
+/// Not suitable for cryptography.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rng {
+    s: [u64; 4],
+}
+impl Value for Rng {}
+
+impl Rng {
+    /// Seed the whole state from one number using splitmix64,
+    /// which never gives the all-zero state.
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Rng { s: [next(), next(), next(), next()] }
+    }
+
+    /// The next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform in `0..n` (without modulo bias), for `n > 0`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        // Lemire's multiply-and-reject
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let m = self.next_u64() as u128 * n as u128;
+            if (m as u64) >= threshold {
+                return (m >> 64) as u64;
+            }
+        }
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    util::add_const_type_builtin::<Rng>(i, "<rng>");
+
+    // seed make-rng -> rng
+    i.add_builtin("make-rng", |i: &mut Interpreter| {
+        let seed = i.stack_pop::<i64>()?.into_inner();
+        i.stack_push(Rng::new(seed as u64));
+        Ok(())
+    });
+
+    // make-rng-os -> rng : seeded differently every time
+    #[cfg(feature = "enable_os")]
+    i.add_builtin("make-rng-os", |i: &mut Interpreter| {
+        use std::hash::{BuildHasher, Hasher};
+        use std::time::{SystemTime, UNIX_EPOCH};
+        // RandomState keys come from the OS
+        let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        h.write_u128(now.as_nanos());
+        i.stack_push(Rng::new(h.finish()));
+        Ok(())
+    });
+
+    // rng rng-i64 -> rng i64 : any i64
+    i.add_builtin("rng-i64", |i: &mut Interpreter| {
+        let mut rng = i.stack_pop::<Rng>()?;
+        let n = rng.as_mut().next_u64() as i64;
+        i.stack_push(rng);
+        i.stack_push(n);
+        Ok(())
+    });
+
+    // rng low high rng-i64-range -> rng i64 : low <= i64 < high,
+    // or a false error if the range is empty
+    i.add_builtin("rng-i64-range", |i: &mut Interpreter| {
+        let high = i.stack_pop::<i64>()?.into_inner();
+        let low = i.stack_pop::<i64>()?.into_inner();
+        let mut rng = i.stack_pop::<Rng>()?;
+        if low >= high {
+            i.stack_push(rng);
+            i.stack_push_error(false);
+            return Ok(());
+        }
+        let span = high.wrapping_sub(low) as u64;
+        let n = low.wrapping_add(rng.as_mut().below(span) as i64);
+        i.stack_push(rng);
+        i.stack_push(n);
+        Ok(())
+    });
+
+    // rng rng-f64 -> rng f64 : 0.0 <= f64 < 1.0
+    i.add_builtin("rng-f64", |i: &mut Interpreter| {
+        let mut rng = i.stack_pop::<Rng>()?;
+        let f = rng.as_mut().next_f64();
+        i.stack_push(rng);
+        i.stack_push(f);
+        Ok(())
+    });
+
+    // rng bytevector rng-bytevector-fill -> rng bytevector : every byte random
+    i.add_builtin("rng-bytevector-fill", |i: &mut Interpreter| {
+        let mut bv = i.stack_pop::<Vec<u8>>()?;
+        let mut rng = i.stack_pop::<Rng>()?;
+        for chunk in bv.as_mut().chunks_mut(8) {
+            let bytes = rng.as_mut().next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        i.stack_push(rng);
+        i.stack_push(bv);
+        Ok(())
+    });
+
+    // rng list rng-list-shuffle -> rng list : in a random order
+    i.add_builtin("rng-list-shuffle", |i: &mut Interpreter| {
+        let l = i.stack_pop::<List>()?.into_inner();
+        let mut rng = i.stack_pop::<Rng>()?;
+        let mut v = l.iter().cloned().collect::<Vec<_>>();
+        // Fisher-Yates
+        for a in (1..v.len()).rev() {
+            let b = rng.as_mut().below(a as u64 + 1) as usize;
+            v.swap(a, b);
+        }
+        i.stack_push(rng);
+        i.stack_push(List::from(v));
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let mut a = Rng::new(1234);
+        let mut b = Rng::new(1234);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            assert!(rng.below(3) < 3);
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+    }
+
+    #[test]
+    fn shuffle() {
+        let l = List::from((0..20).map(Val::from).collect::<Vec<_>>());
+        let mut i = Interpreter::new(vec![
+            5.into(), "make-rng".to_symbol().into(),
+            l.into(), "rng-list-shuffle".to_symbol().into(),
+            "swap".to_symbol().into(),
+            i64::MIN.into(), i64::MAX.into(), "rng-i64-range".to_symbol().into(),
+        ]);
+        install(&mut i);
+        crate::builtins::core::install(&mut i);
+        assert!(i.run().is_ok());
+        i.stack_pop::<i64>().ok().unwrap();
+        i.stack_pop::<Rng>().ok().unwrap();
+        let shuffled = i.stack_pop::<List>().ok().unwrap().into_inner();
+        let mut got = shuffled.iter()
+            .map(|v| *v.downcast_ref::<i64>().unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(got, (0..20).collect::<Vec<_>>());
+        got.sort();
+        assert_eq!(got, (0..20).collect::<Vec<_>>());
+    }
+}
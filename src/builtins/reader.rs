@@ -1,16 +1,145 @@
-
 //! Reading code
 
+use std::cell::RefCell;
 use crate::base::*;
 use crate::reader::*;
 use crate::interpreter::*;
+use crate::printer::Printer;
 use crate::builtins::util;
+#[cfg(feature = "enable_fs_os")]
+use crate::compiled;
+#[cfg(feature = "enable_fs_os")]
+use std::path::PathBuf;
+
+/// Where [read_cached] keeps compiled code: `$WORST_CACHE_DIR`,
+/// or [None] (no caching) if that isn't set or is empty.
+#[cfg(feature = "enable_fs_os")]
+pub fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("WORST_CACHE_DIR")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Read some code with the given source name,
+/// caching it in the [cache_dir] if there is one (see [read_cached_in]).
+pub fn read_cached(src: &str, name: &str, uniques: &mut UniqueGen) -> Result<List, ReadError> {
+    #[cfg(feature = "enable_fs_os")]
+    return read_cached_in(cache_dir().as_deref(), src, name, uniques);
+    #[cfg(not(feature = "enable_fs_os"))]
+    read_code(src, name, uniques)
+}
+
+/// Read some code with the given source name,
+/// using a [compiled] copy from `dir` if the same code
+/// has been read before, and saving one otherwise.
+/// Caching is best-effort: if the cache can't be read or written,
+/// it just reads the code.
+#[cfg(feature = "enable_fs_os")]
+pub fn read_cached_in(dir: Option<&std::path::Path>, src: &str, name: &str,
+                      uniques: &mut UniqueGen) -> Result<List, ReadError> {
+    let cached = dir.map(|dir| {
+        dir.join(format!("{:016x}.wc", compiled::source_hash(src, name)))
+    });
+    if let Some(code) = cached.as_ref()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|data| compiled::decode(&data, uniques).ok()) {
+        return Ok(code);
+    }
+
+    let code = read_code(src, name, uniques)?;
+
+    if let (Some(path), Ok(data)) = (cached, compiled::encode(&code, uniques)) {
+        // write then rename so nobody reads half a file
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let _ = path.parent().map(std::fs::create_dir_all);
+        if std::fs::write(&tmp, data).is_ok() && std::fs::rename(&tmp, &path).is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+    Ok(code)
+}
+
+fn read_code(src: &str, name: &str, uniques: &mut UniqueGen) -> Result<List, ReadError> {
+    let mut reader = Reader::new(uniques);
+    reader.set_source_name(name);
+    Ok(List::from(reader.read_all(&mut src.chars())?))
+}
+
+// A hash handler function that evaluates body in a new interpreter
+// with the current definitions, the rest of the atom and (if any)
+// the value read after it on the stack, and reads whatever it leaves (if any).
+fn hash_handler_fn(i: &Interpreter, body: Val)
+    -> impl Fn(&str, Option<Val>) -> HashResult {
+    let defs = i.defenv_ref().clone();
+    let inner = RefCell::new(i.new_inner_empty());
+    move |token: &str, v: Option<Val>| {
+        let mut inner = inner.try_borrow_mut()
+            .map_err(|_| "hash handler used while already reading".to_string())?;
+        inner.reset();
+        *inner.stack_mut() = List::default();
+        *inner.defenv_mut() = defs.clone();
+        inner.stack_push(token.to_string());
+        if let Some(v) = v {
+            inner.stack_push(v);
+        }
+        let printer = Printer::new(inner.uniques_mut());
+        inner.eval_any_next(body.clone())
+            .and_then(|()| inner.run())
+            .map_err(|e| printer.print(&e))?;
+        match inner.stack_ref().len() {
+            0 => Ok(None),
+            1 => Ok(inner.stack_mut().pop()),
+            _ => Err("hash handler left more than one value".to_string()),
+        }
+    }
+}
+
+// reader c body -> reader c body
+fn pop_hash_handler(i: &mut Interpreter) -> BuiltinRet<(ValOf<Reader>, char, Val)> {
+    let body = i.stack_pop_val()?;
+    let s = i.stack_pop::<String>()?;
+    let mut chars = s.as_ref().chars();
+    let c = chars.next().filter(|_| chars.next().is_none());
+    let Some(c) = c else { return Err(i.make_error("not-a-character", s)) };
+    let r = i.stack_pop::<Reader>()?;
+    Ok((r, c, body))
+}
 
 /// Install a bunch of reader functions.
 pub fn install(i: &mut Interpreter) {
-    i.add_builtin("reader-empty", util::make_default::<Reader>);
+    i.add_builtin("reader-empty", |i: &mut Interpreter| {
+        let r = Reader::new(i.uniques_mut());
+        i.stack_push(r);
+        Ok(())
+    });
     util::add_const_type_builtin::<Reader>(i, "<reader>");
 
+    i.add_builtin("reader-set-source-name", |i: &mut Interpreter| {
+        let name = i.stack_pop::<String>()?;
+        let mut r = i.stack_pop::<Reader>()?;
+        r.as_mut().set_source_name(name.as_ref());
+        i.stack_push(r);
+        Ok(())
+    });
+    // reader c body reader-hash-atom-handler-set -> reader :
+    // read #c and the rest of an atom by evaluating body with the rest
+    // on the stack, reading whatever value it leaves (or nothing)
+    i.add_builtin("reader-hash-atom-handler-set", |i: &mut Interpreter| {
+        let (mut r, c, body) = pop_hash_handler(i)?;
+        let f = hash_handler_fn(i, body);
+        r.as_mut().set_hash_handler(c, HashHandler::atom(move |token| f(token, None)));
+        i.stack_push(r);
+        Ok(())
+    });
+    // reader c body reader-hash-datum-handler-set -> reader :
+    // the same, but the value read after the atom is also on the stack
+    i.add_builtin("reader-hash-datum-handler-set", |i: &mut Interpreter| {
+        let (mut r, c, body) = pop_hash_handler(i)?;
+        let f = hash_handler_fn(i, body);
+        r.as_mut().set_hash_handler(c, HashHandler::datum(move |token, v| f(token, Some(v))));
+        i.stack_push(r);
+        Ok(())
+    });
     i.add_builtin("reader-complete", |i: &mut Interpreter| {
         let r = i.stack_pop::<Reader>()?.into_inner();
         match r.complete() {
@@ -34,10 +163,101 @@ pub fn install(i: &mut Interpreter) {
 
     i.add_builtin("read-string->list", |i: &mut Interpreter| {
         let mut s = i.stack_pop::<String>()?;
-        i.stack_push_result(read_all(&mut s.as_mut().chars())
+        let r = Reader::new(i.uniques_mut());
+        i.stack_push_result(r.read_all(&mut s.as_mut().chars())
+                            .map(List::from)
+                            .map_err(|e| format!("{:?}", e)));
+        Ok(())
+    });
+    // string name read-named-string->list -> list
+    // like read-string->list, but with the name in every source span
+    i.add_builtin("read-named-string->list", |i: &mut Interpreter| {
+        let name = i.stack_pop::<String>()?;
+        let mut s = i.stack_pop::<String>()?;
+        let mut r = Reader::new(i.uniques_mut());
+        r.set_source_name(name.as_ref());
+        i.stack_push_result(r.read_all(&mut s.as_mut().chars())
                             .map(List::from)
                             .map_err(|e| format!("{:?}", e)));
         Ok(())
     });
+
+    // string name read-cached-string->list -> list
+    // like read-named-string->list, but cached on disk (see read_cached)
+    i.add_builtin("read-cached-string->list", |i: &mut Interpreter| {
+        let name = i.stack_pop::<String>()?;
+        let s = i.stack_pop::<String>()?;
+        let r = read_cached(s.as_ref(), name.as_ref(), i.uniques_mut());
+        i.stack_push_result(r.map_err(|e| format!("{:?}", e)));
+        Ok(())
+    });
+
+    util::add_const_type_builtin::<SourceSpan>(i, "<source-span>");
+    i.add_builtin("value-source-span", |i: &mut Interpreter| {
+        let v = i.stack_pop_val()?;
+        let u = i.uniques_mut().get_type::<SourceSpan>();
+        i.stack_push_option(v.meta_ref().get_val(&u));
+        Ok(())
+    });
+    // list list-brackets -> symbol|#f :
+    // round, square or curly, if the list was read by a reader
+    i.add_builtin("list-brackets", |i: &mut Interpreter| {
+        let l = i.stack_pop::<List>()?;
+        let u = i.uniques_mut().get_type::<Brackets>();
+        let b = l.meta_ref().get_ref::<Brackets>(&u).map(|b| b.name().to_symbol());
+        i.stack_push_option(b);
+        Ok(())
+    });
+    i.add_builtin("source-span-name", |i: &mut Interpreter| {
+        let span = i.stack_pop::<SourceSpan>()?;
+        i.stack_push_option(span.as_ref().source.as_deref().map(String::from));
+        Ok(())
+    });
+    // span source-span-start -> line column
+    i.add_builtin("source-span-start", |i: &mut Interpreter| {
+        let span = i.stack_pop::<SourceSpan>()?;
+        let start = span.as_ref().start;
+        i.stack_push(start.line as i64);
+        i.stack_push(start.column as i64);
+        Ok(())
+    });
+    // span source-span-end -> line column
+    i.add_builtin("source-span-end", |i: &mut Interpreter| {
+        let span = i.stack_pop::<SourceSpan>()?;
+        let end = span.as_ref().end;
+        i.stack_push(end.line as i64);
+        i.stack_push(end.column as i64);
+        Ok(())
+    });
+    i.add_builtin("source-span->string", |i: &mut Interpreter| {
+        let span = i.stack_pop::<SourceSpan>()?;
+        i.stack_push(span.as_ref().to_string());
+        Ok(())
+    });
 }
 
+
+#[cfg(all(test, feature = "enable_fs_os"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cache_in_temp_dir() {
+        let dir = std::env::temp_dir().join(format!("worst-cache-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut uniques = UniqueGen::default();
+        let read = |uniques: &mut UniqueGen| {
+            let code = read_cached_in(Some(&dir), "a (b 1)", "test.w", uniques).ok().unwrap();
+            code.iter().map(|v| Printer::default().print(v)).collect::<Vec<_>>()
+        };
+        assert_eq!(read(&mut uniques), vec!["a", "(b 1)"]);
+        let files = std::fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with(".wc"));
+        // again, from the cache
+        assert_eq!(read(&mut uniques), vec!["a", "(b 1)"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+//! Regular expressions ([Regex]), using the syntax of the `regex` crate.
+//!
+//! Builtins take the string to search first and then the regex,
+//! like `string pattern string-find`.
+
+use ::regex::Regex;
+use crate::base::*;
+use crate::builtins::util;
+use crate::interpreter::*;
+
+impl Value for Regex {}
+
+// string regex -> string regex
+fn pop_subject(i: &mut Interpreter) -> BuiltinRet<(String, Regex)> {
+    let re = i.stack_pop::<Regex>()?.into_inner();
+    let s = i.stack_pop::<String>()?.into_inner();
+    Ok((s, re))
+}
+
+/// Install all these functions.
+pub fn install(i: &mut Interpreter) {
+    util::add_const_type_builtin::<Regex>(i, "<regex>");
+
+    // pattern regex-compile -> regex, or an error? message if the pattern is bad
+    i.add_builtin("regex-compile", |i: &mut Interpreter| {
+        let p = i.stack_pop::<String>()?;
+        i.stack_push_result(Regex::new(p.as_ref()).map_err(|e| e.to_string()));
+        Ok(())
+    });
+    // regex regex->string -> pattern
+    i.add_builtin("regex->string", |i: &mut Interpreter| {
+        let re = i.stack_pop::<Regex>()?;
+        i.stack_push(re.as_ref().as_str().to_string());
+        Ok(())
+    });
+
+    // string regex regex-is-match -> bool : whether it matches anywhere in string
+    i.add_builtin("regex-is-match", |i: &mut Interpreter| {
+        let (s, re) = pop_subject(i)?;
+        i.stack_push(re.is_match(&s));
+        Ok(())
+    });
+    // string regex regex-find -> string : the first match, or false
+    i.add_builtin("regex-find", |i: &mut Interpreter| {
+        let (s, re) = pop_subject(i)?;
+        i.stack_push_option(re.find(&s).map(|m| m.as_str().to_string()));
+        Ok(())
+    });
+    // string regex regex-find-all -> list : every (non-overlapping) match
+    i.add_builtin("regex-find-all", |i: &mut Interpreter| {
+        let (s, re) = pop_subject(i)?;
+        let all = re.find_iter(&s).map(|m| m.as_str().to_string());
+        i.stack_push(List::from_iter(all));
+        Ok(())
+    });
+    // string regex regex-captures -> list : the groups in the first match, or false.
+    // The whole match is first, and groups that didn't match are false.
+    i.add_builtin("regex-captures", |i: &mut Interpreter| {
+        let (s, re) = pop_subject(i)?;
+        let caps = re.captures(&s).map(|c| {
+            List::from(c.iter().map(|m| match m {
+                Some(m) => Val::from(m.as_str().to_string()),
+                None => Val::from(false),
+            }).collect::<Vec<_>>())
+        });
+        i.stack_push_option(caps);
+        Ok(())
+    });
+    // string regex replacement regex-replace-all -> string
+    // replacement can refer to groups as $1 or ${name}
+    i.add_builtin("regex-replace-all", |i: &mut Interpreter| {
+        let rep = i.stack_pop::<String>()?.into_inner();
+        let (s, re) = pop_subject(i)?;
+        i.stack_push(re.replace_all(&s, rep.as_str()).into_owned());
+        Ok(())
+    });
+    // string regex regex-split -> list : the parts between matches
+    i.add_builtin("regex-split", |i: &mut Interpreter| {
+        let (s, re) = pop_subject(i)?;
+        i.stack_push(List::from_iter(re.split(&s).map(String::from)));
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures() {
+        let mut i = Interpreter::new(vec![
+            "(".to_string().into(), "regex-compile".to_symbol().into(),
+            "k=v, x=".to_string().into(),
+            r"(\w)=(\w)?".to_string().into(), "regex-compile".to_symbol().into(),
+            "regex-captures".to_symbol().into(),
+        ]);
+        install(&mut i);
+        assert!(i.run().is_ok());
+        let caps = i.stack_pop::<List>().ok().unwrap().into_inner();
+        let caps = caps.iter()
+            .map(|v| v.downcast_ref::<String>().cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(caps, vec![ Some("k=v".into()), Some("k".into()), Some("v".into()) ]);
+        // bad pattern
+        assert!(i.stack_pop::<String>().is_ok());
+    }
+}
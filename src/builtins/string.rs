@@ -1,10 +1,38 @@
 
 //! Strings (of utf8 characters)
+//!
+//! Lengths and indices count characters (Unicode scalar values), not bytes,
+//! except for `string-byte-length`.
 
 use crate::base::*;
 use super::util;
 use crate::interpreter::*;
 
+/// The longest string (in bytes) that `string-repeat` will make.
+pub const MAX_REPEAT_BYTES: usize = 1 << 30;
+
+// Byte offset of the given character index (clipped to the end).
+fn char_to_byte(s: &str, idx: usize) -> usize {
+    s.char_indices().nth(idx).map(|(b, _)| b).unwrap_or(s.len())
+}
+
+fn str_binop<R: Into<Val>>(f: impl Fn(&str, &str) -> R) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let b = i.stack_pop::<String>()?;
+        let a = i.stack_pop::<String>()?;
+        i.stack_push(f(a.as_ref(), b.as_ref()));
+        Ok(())
+    }
+}
+
+fn str_unop<R: Into<Val>>(f: impl Fn(&str) -> R) -> impl Fn(&mut Interpreter) -> BuiltinRet {
+    move |i| {
+        let a = i.stack_pop::<String>()?;
+        i.stack_push(f(a.as_ref()));
+        Ok(())
+    }
+}
+
 /// Install some string functions.
 pub fn install(i: &mut Interpreter) {
     util::add_const_type_builtin::<String>(i, "<string>");
@@ -25,6 +53,113 @@ pub fn install(i: &mut Interpreter) {
         i.stack_push(List::from_iter(split));
         Ok(())
     });
+    // list-of-strings separator string-join -> string
+    i.add_builtin("string-join", |i: &mut Interpreter| {
+        let sep = i.stack_pop::<String>()?;
+        let l = i.stack_pop::<List>()?;
+        let mut parts = vec![];
+        for v in l.as_ref().iter() {
+            match v.downcast_ref::<String>() {
+                Some(s) => parts.push(s.as_str()),
+                None => return Err(i.wrong_type_error::<String>(v.clone())),
+            }
+        }
+        let joined = parts.join(sep.as_ref());
+        i.stack_push(joined);
+        Ok(())
+    });
+    // string count string-repeat -> string
+    // (errors with too-long if it would be over MAX_REPEAT_BYTES)
+    i.add_builtin("string-repeat", |i: &mut Interpreter| {
+        let count = i.stack_pop::<i64>()?.into_inner();
+        let s = i.stack_pop::<String>()?;
+        let n = count.max(0) as usize;
+        match s.as_ref().len().checked_mul(n) {
+            Some(len) if len <= MAX_REPEAT_BYTES => {
+                i.stack_push(s.as_ref().repeat(n));
+                Ok(())
+            },
+            _ => Err(i.make_error("too-long", List::from(vec![
+                s.into(), count.into(),
+            ]))),
+        }
+    });
+
+    i.add_builtin("string-length", str_unop(|s| s.chars().count() as i64));
+    i.add_builtin("string-byte-length", str_unop(|s| s.len() as i64));
+    // string start end string-range -> string
+    // by character index, from the end if < 0, clipped to the string
+    i.add_builtin("string-range", |i: &mut Interpreter| {
+        let end = i.stack_pop::<i64>()?.into_inner();
+        let start = i.stack_pop::<i64>()?.into_inner();
+        let s = i.stack_pop::<String>()?;
+        let s = s.as_ref();
+        let len = s.chars().count();
+        let start = util::index_range(len, start, false);
+        let end = util::index_range(len, end, false).max(start);
+        let (a, b) = (char_to_byte(s, start), char_to_byte(s, end));
+        i.stack_push(s[a..b].to_string());
+        Ok(())
+    });
+    // string pattern string-find -> i64 : character index of the first pattern, or false
+    i.add_builtin("string-find", |i: &mut Interpreter| {
+        let p = i.stack_pop::<String>()?;
+        let s = i.stack_pop::<String>()?;
+        let s = s.as_ref();
+        let found = s.find(p.as_ref()).map(|b| s[..b].chars().count() as i64);
+        i.stack_push_option(found);
+        Ok(())
+    });
+    i.add_builtin("string-contains", str_binop(|a, b| a.contains(b)));
+    i.add_builtin("string-starts-with", str_binop(|a, b| a.starts_with(b)));
+    i.add_builtin("string-ends-with", str_binop(|a, b| a.ends_with(b)));
+    // string from to string-replace -> string : every from replaced by to
+    i.add_builtin("string-replace", |i: &mut Interpreter| {
+        let to = i.stack_pop::<String>()?;
+        let from = i.stack_pop::<String>()?;
+        let s = i.stack_pop::<String>()?;
+        i.stack_push(s.as_ref().replace(from.as_ref(), to.as_ref()));
+        Ok(())
+    });
+    i.add_builtin("string-trim", str_unop(|s| s.trim().to_string()));
+    i.add_builtin("string-trim-start", str_unop(|s| s.trim_start().to_string()));
+    i.add_builtin("string-trim-end", str_unop(|s| s.trim_end().to_string()));
+    i.add_builtin("string-upcase", str_unop(str::to_uppercase));
+    i.add_builtin("string-downcase", str_unop(str::to_lowercase));
+
+    // string string->chars -> list of one-character strings
+    i.add_builtin("string->chars", str_unop(|s| {
+        List::from_iter(s.chars().map(String::from))
+    }));
+    // string string->codepoints -> list of i64
+    i.add_builtin("string->codepoints", str_unop(|s| {
+        List::from_iter(s.chars().map(|c| c as i64))
+    }));
+    // list-of-i64 codepoints->string -> string, or false error? if any are invalid
+    i.add_builtin("codepoints->string", |i: &mut Interpreter| {
+        let l = i.stack_pop::<List>()?;
+        let s = l.as_ref().iter().map(|v| {
+            v.downcast_ref::<i64>()
+                .and_then(|c| u32::try_from(*c).ok())
+                .and_then(char::from_u32)
+        }).collect::<Option<String>>();
+        i.stack_push_opterr(s);
+        Ok(())
+    });
+
+    // string string->i64 -> i64, or false error? if it isn't one
+    i.add_builtin("string->i64", |i: &mut Interpreter| {
+        let s = i.stack_pop::<String>()?;
+        i.stack_push_opterr(s.as_ref().trim().parse::<i64>().ok());
+        Ok(())
+    });
+    // string string->f64 -> f64, or false error? if it isn't one
+    i.add_builtin("string->f64", |i: &mut Interpreter| {
+        let s = i.stack_pop::<String>()?;
+        i.stack_push_opterr(s.as_ref().trim().parse::<f64>().ok());
+        Ok(())
+    });
+
     i.add_builtin("whitespace?", |i: &mut Interpreter| {
         let s = i.stack_pop::<String>()?;
         let ws = s.as_ref().chars().all(char::is_whitespace);
@@ -44,3 +179,63 @@ pub fn install(i: &mut Interpreter) {
     });
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(code: Vec<Val>) -> Interpreter {
+        let mut i = Interpreter::new(code);
+        install(&mut i);
+        assert!(i.run().is_ok());
+        i
+    }
+
+    fn pop_string(i: &mut Interpreter) -> String {
+        i.stack_pop::<String>().ok().unwrap().into_inner()
+    }
+
+    #[test]
+    fn char_indices() {
+        let mut i = run(vec![
+            "héllo wörld".to_string().into(), 1.into(), (-3).into(), "string-range".to_symbol().into(),
+            "héllo wörld".to_string().into(), "wö".to_string().into(), "string-find".to_symbol().into(),
+            "héllo".to_string().into(), "string-length".to_symbol().into(),
+            "héllo".to_string().into(), "string-byte-length".to_symbol().into(),
+        ]);
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 6);
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 5);
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 6);
+        assert_eq!(pop_string(&mut i), "éllo wö");
+    }
+
+    #[test]
+    fn parse_numbers() {
+        let mut i = run(vec![
+            " 42 ".to_string().into(), "string->i64".to_symbol().into(),
+            "4.5e1".to_string().into(), "string->f64".to_symbol().into(),
+            "nope".to_string().into(), "string->i64".to_symbol().into(),
+        ]);
+        assert!(!i.stack_pop::<bool>().ok().unwrap().into_inner());
+        assert_eq!(i.stack_pop::<f64>().ok().unwrap().into_inner(), 45.0);
+        assert_eq!(i.stack_pop::<i64>().ok().unwrap().into_inner(), 42);
+    }
+
+    #[test]
+    fn repeat_too_long() {
+        let mut i = Interpreter::new(vec![
+            "ab".to_string().into(), i64::MAX.into(), "string-repeat".to_symbol().into(),
+        ]);
+        install(&mut i);
+        assert!(i.run().is_err());
+    }
+
+    #[test]
+    fn join_chars() {
+        let mut i = run(vec![
+            "añb".to_string().into(), "string->chars".to_symbol().into(),
+            "-".to_string().into(), "string-join".to_symbol().into(),
+        ]);
+        assert_eq!(pop_string(&mut i), "a-ñ-b");
+    }
+}
@@ -69,7 +69,7 @@ pub fn value_hash<T: Value + Hash>(i: &mut Interpreter) -> BuiltinRet {
     let mut hasher = DefaultHasher::new();
     v.as_ref().hash(&mut hasher);
     // just the bytes please
-    let u = unsafe { std::mem::transmute::<u64, i64>(hasher.finish()) };
+    let u = hasher.finish() as i64;
     i.stack_push(u);
     Ok(())
 }
@@ -0,0 +1,378 @@
+//! A compact binary form of code that has already been read,
+//! so it doesn't need to be read again (see [encode] and [decode]).
+//!
+//! Only what the [Reader](crate::reader::Reader) produces can be encoded:
+//! [bool], [i64], [BigInt], [f64], [String], bytevector, [Symbol] and [List] values,
+//! each with an optional [SourceSpan],
+//! and lists with their [Brackets].
+//!
+//! This module is also used by the build script to compile the embedded
+//! `lib/` directory, so it only depends on [base](crate::base) and
+//! [reader](crate::reader).
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+use num_bigint::BigInt;
+use crate::base::*;
+use crate::reader::{ Brackets, Position, SourceSpan };
+
+const MAGIC: &[u8] = b"WRSTCODE";
+/// Format version, changed whenever the encoding changes.
+pub const VERSION: u64 = 4;
+
+const FALSE: u8 = 0;
+const TRUE: u8 = 1;
+const I64: u8 = 2;
+const F64: u8 = 3;
+const STRING: u8 = 4;
+const SYMBOL: u8 = 5;
+const LIST: u8 = 6;
+const BIGINT: u8 = 7;
+const BYTES: u8 = 8;
+
+// List brackets
+const NO_BRACKETS: u8 = 0;
+const ROUND: u8 = 1;
+const SQUARE: u8 = 2;
+const CURLY: u8 = 3;
+
+/// Why code couldn't be encoded or decoded.
+#[derive(Debug, Clone)]
+pub enum CompiledError {
+    /// A value that isn't code, or has meta other than
+    /// a [SourceSpan] or [Brackets].
+    NotCode,
+    /// The data isn't compiled code, or is damaged or from another version.
+    Malformed(String),
+}
+impl Value for CompiledError {}
+
+impl Display for CompiledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CompiledError::NotCode => write!(f, "not code"),
+            CompiledError::Malformed(s) => write!(f, "malformed compiled code: {s}"),
+        }
+    }
+}
+
+/// Encode the given code.
+/// `uniques` should be the same one given to the
+/// [Reader](crate::reader::Reader) that read it.
+pub fn encode(code: &List, uniques: &UniqueGen) -> Result<Vec<u8>, CompiledError> {
+    let mut e = Encoder {
+        out: MAGIC.to_vec(),
+        span: uniques.lookup_type::<SourceSpan>(),
+        brackets: uniques.lookup_type::<Brackets>(),
+        names: HashMap::new(),
+    };
+    e.uint(VERSION);
+    e.list(code)?;
+    Ok(e.out)
+}
+
+/// Decode code previously given by [encode],
+/// with the [SourceSpan]s and [Brackets] keyed using `uniques`.
+pub fn decode(data: &[u8], uniques: &mut UniqueGen) -> Result<List, CompiledError> {
+    let data = data.strip_prefix(MAGIC).ok_or_else(|| malformed("not compiled code"))?;
+    let mut d = Decoder {
+        data, pos: 0,
+        span: uniques.get_type::<SourceSpan>(),
+        brackets: uniques.get_type::<Brackets>(),
+        names: vec![],
+    };
+    let version = d.uint()?;
+    if version != VERSION {
+        return Err(malformed(&format!("unknown version {version}")));
+    }
+    let code = d.list()?;
+    if d.pos != d.data.len() {
+        return Err(malformed("trailing data"));
+    }
+    Ok(code)
+}
+
+/// A hash of some source code and its name, suitable for naming a cache entry.
+/// It is the same on every platform and build, and changes with [VERSION].
+pub fn source_hash(src: &str, name: &str) -> u64 {
+    // FNV-1a
+    let mut h: u64 = 0xcbf29ce484222325;
+    let version = VERSION.to_le_bytes();
+    let name_len = (name.len() as u64).to_le_bytes();
+    for part in [&version[..], &name_len[..], name.as_bytes(), src.as_bytes()] {
+        for b in part {
+            h ^= *b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+    }
+    h
+}
+
+fn malformed(what: &str) -> CompiledError {
+    CompiledError::Malformed(what.to_string())
+}
+
+struct Encoder {
+    out: Vec<u8>,
+    span: Option<Unique>,
+    brackets: Option<Unique>,
+    // source names, by first appearance
+    names: HashMap<Rc<str>, u64>,
+}
+
+impl Encoder {
+    fn byte(&mut self, b: u8) { self.out.push(b); }
+
+    // LEB128
+    fn uint(&mut self, mut n: u64) {
+        loop {
+            let b = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.byte(b);
+                return;
+            }
+            self.byte(b | 0x80);
+        }
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.uint(b.len() as u64);
+        self.out.extend_from_slice(b);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn list(&mut self, l: &List) -> Result<(), CompiledError> {
+        self.uint(l.len() as u64);
+        for v in l.iter() {
+            self.val(v)?;
+        }
+        Ok(())
+    }
+
+    fn val(&mut self, v: &Val) -> Result<(), CompiledError> {
+        if let Some(b) = v.downcast_ref::<bool>() {
+            self.byte(if *b { TRUE } else { FALSE });
+        } else if let Some(n) = v.downcast_ref::<i64>() {
+            self.byte(I64);
+            self.out.extend_from_slice(&n.to_le_bytes());
+        } else if let Some(n) = v.downcast_ref::<BigInt>() {
+            self.byte(BIGINT);
+            self.bytes(&n.to_signed_bytes_le());
+        } else if let Some(n) = v.downcast_ref::<f64>() {
+            self.byte(F64);
+            self.out.extend_from_slice(&n.to_bits().to_le_bytes());
+        } else if let Some(s) = v.downcast_ref::<String>() {
+            self.byte(STRING);
+            self.str(s);
+        } else if let Some(b) = v.downcast_ref::<Vec<u8>>() {
+            self.byte(BYTES);
+            self.bytes(b);
+        } else if let Some(s) = v.downcast_ref::<Symbol>() {
+            self.byte(SYMBOL);
+            self.str(s.as_ref());
+        } else if let Some(l) = v.downcast_ref::<List>() {
+            self.byte(LIST);
+            self.list(l)?;
+            let brackets = self.brackets.as_ref()
+                .and_then(|u| v.meta_ref().get_ref::<Brackets>(u));
+            self.byte(match brackets {
+                None => NO_BRACKETS,
+                Some(Brackets::Round) => ROUND,
+                Some(Brackets::Square) => SQUARE,
+                Some(Brackets::Curly) => CURLY,
+            });
+        } else {
+            return Err(CompiledError::NotCode);
+        }
+        self.span(v.meta_ref())
+    }
+
+    // 0 for no span, or 1 + the source name's index (or 0 then the name),
+    // then the start and end positions
+    fn span(&mut self, meta: &Meta) -> Result<(), CompiledError> {
+        let span = self.span.as_ref().and_then(|u| meta.get_ref::<SourceSpan>(u)).cloned();
+        let brackets = self.brackets.as_ref().is_some_and(|u| meta.contains_val(u));
+        if meta.len() != usize::from(span.is_some()) + usize::from(brackets) {
+            return Err(CompiledError::NotCode);
+        }
+        let Some(span) = span else {
+            self.byte(0);
+            return Ok(());
+        };
+        self.byte(1);
+        match &span.source {
+            Some(name) => match self.names.get(name) {
+                Some(idx) => self.uint(idx + 2),
+                None => {
+                    self.names.insert(name.clone(), self.names.len() as u64);
+                    self.uint(1);
+                    self.str(name);
+                },
+            },
+            None => self.uint(0),
+        }
+        for p in [span.start, span.end] {
+            self.uint(p.line as u64);
+            self.uint(p.column as u64);
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    span: Unique,
+    brackets: Unique,
+    names: Vec<Rc<str>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CompiledError> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.data.len())
+            .ok_or_else(|| malformed("unexpected end"))?;
+        let b = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(b)
+    }
+
+    fn byte(&mut self) -> Result<u8, CompiledError> { Ok(self.take(1)?[0]) }
+
+    fn uint(&mut self) -> Result<u64, CompiledError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            n |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(malformed("number too long"))
+    }
+
+    fn len(&mut self) -> Result<usize, CompiledError> {
+        let n = self.uint()? as usize;
+        // every item takes at least a byte
+        if n > self.data.len() - self.pos {
+            return Err(malformed("length too long"));
+        }
+        Ok(n)
+    }
+
+    fn u64_le(&mut self) -> Result<u64, CompiledError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<String, CompiledError> {
+        let n = self.len()?;
+        String::from_utf8(self.take(n)?.to_vec()).map_err(|_| malformed("invalid string"))
+    }
+
+    fn list(&mut self) -> Result<List, CompiledError> {
+        let mut l = vec![];
+        for _ in 0..self.len()? {
+            l.push(self.val()?);
+        }
+        Ok(List::from(l))
+    }
+
+    fn val(&mut self) -> Result<Val, CompiledError> {
+        let mut v: Val = match self.byte()? {
+            FALSE => false.into(),
+            TRUE => true.into(),
+            I64 => (self.u64_le()? as i64).into(),
+            BIGINT => {
+                let n = self.len()?;
+                BigInt::from_signed_bytes_le(self.take(n)?).into()
+            },
+            F64 => f64::from_bits(self.u64_le()?).into(),
+            STRING => self.str()?.into(),
+            BYTES => {
+                let n = self.len()?;
+                self.take(n)?.to_vec().into()
+            },
+            SYMBOL => Symbol::from(self.str()?).into(),
+            LIST => {
+                let mut l = Val::from(self.list()?);
+                let brackets = match self.byte()? {
+                    NO_BRACKETS => None,
+                    ROUND => Some(Brackets::Round),
+                    SQUARE => Some(Brackets::Square),
+                    CURLY => Some(Brackets::Curly),
+                    _ => return Err(malformed("unknown brackets")),
+                };
+                if let Some(b) = brackets {
+                    l.meta_mut().insert_val(self.brackets.clone(), b.into());
+                }
+                l
+            },
+            _ => return Err(malformed("unknown value")),
+        };
+        if self.byte()? != 0 {
+            let source = match self.uint()? {
+                0 => None,
+                1 => {
+                    let name = Rc::<str>::from(self.str()?);
+                    self.names.push(name.clone());
+                    Some(name)
+                },
+                n => Some(self.names.get(n as usize - 2).cloned()
+                          .ok_or_else(|| malformed("bad source name"))?),
+            };
+            let mut pos = || -> Result<Position, CompiledError> {
+                Ok(Position { line: self.uint()? as usize, column: self.uint()? as usize })
+            };
+            let start = pos()?;
+            let end = pos()?;
+            let span = SourceSpan { source, start, end };
+            v.meta_mut().insert_val(self.span.clone(), span.into());
+        }
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::Reader;
+
+    #[test]
+    fn round_trip() {
+        let mut uniques = UniqueGen::default();
+        let mut reader = Reader::new(&uniques);
+        reader.set_source_name("test.w");
+        let code = reader.read_all(&mut "1 [2.5 \"three\"] #t four -0x1_0000_0000_0000_0000 #b\"\\xff\"".chars()).ok().unwrap();
+        let data = encode(&List::from(code), &uniques).ok().unwrap();
+
+        let code = decode(&data, &mut uniques).ok().unwrap();
+        let span = uniques.get_type::<SourceSpan>();
+        let spans = code.iter()
+            .map(|v| v.meta_ref().get_ref::<SourceSpan>(&span).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec!["test.w:1:1", "test.w:1:3", "test.w:1:17", "test.w:1:20", "test.w:1:25", "test.w:1:50"]);
+        let inner = code.get(1).unwrap().downcast_ref::<List>().unwrap();
+        assert_eq!(inner.get(0).unwrap().downcast_ref::<f64>(), Some(&2.5));
+        assert_eq!(inner.get(1).unwrap().downcast_ref::<String>().unwrap(), "three");
+        assert_eq!(code.get(3).unwrap().downcast_ref::<Symbol>(), Some(&"four".into()));
+        assert_eq!(code.get(5).unwrap().downcast_ref::<Vec<u8>>(), Some(&vec![255]));
+        assert_eq!(code.get(4).unwrap().downcast_ref::<BigInt>(), Some(&-(BigInt::from(1) << 64usize)));
+        let brackets = uniques.get_type::<Brackets>();
+        assert_eq!(code.get(1).unwrap().meta_ref().get_ref::<Brackets>(&brackets),
+                   Some(&Brackets::Square));
+
+        assert!(matches!(decode(&data[..data.len() - 1], &mut uniques),
+                         Err(CompiledError::Malformed(_))));
+    }
+
+    #[test]
+    fn not_code() {
+        let uniques = UniqueGen::default();
+        let code = List::from(vec![Val::from(Place::wrap(1))]);
+        assert!(matches!(encode(&code, &uniques), Err(CompiledError::NotCode)));
+    }
+}
@@ -0,0 +1,267 @@
+//! A line-based interactive debugger that drives an [Interpreter]
+//! one step at a time.
+
+use std::collections::BTreeSet;
+use std::io::{ self, BufRead, Write };
+use crate::base::*;
+use crate::interpreter::*;
+use crate::printer::Printer;
+use crate::reader::SourceSpan;
+
+/// When to stop and ask for the next command.
+#[derive(Clone, Copy)]
+enum Mode {
+    /// After every step.
+    Step,
+    /// When back at or above the given frame depth.
+    Next(usize),
+    /// When above the given frame depth.
+    Out(usize),
+    /// Only at breakpoints.
+    Continue,
+}
+
+/// Debugger state: breakpoints and what to do next.
+/// Breakpoints are definition names (the [Symbol] meta attached by `define`)
+/// and are hit whenever a stack frame for that definition is entered.
+pub struct Debugger {
+    breakpoints: BTreeSet<String>,
+    mode: Mode,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger { breakpoints: BTreeSet::new(), mode: Mode::Step }
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  s, step             evaluate one thing
+  n, next             step over the next thing in the current frame
+  o, out              run until the current frame is finished
+  c, continue         run until a breakpoint
+  b, break NAME       stop whenever definition NAME is entered
+  d, delete NAME      remove a breakpoint
+  breakpoints         list breakpoints
+  stack               show the stack
+  bt, backtrace       show stack frames (0 is the current frame)
+  l, locals [N]       show local definition names in frame N (default 0)
+  w, where            show what's next in the current frame
+  q, quit             stop debugging and leave the rest of the code unrun
+  h, help             show this message
+";
+
+impl Debugger {
+    /// Add a breakpoint on the definition with the given name.
+    pub fn add_breakpoint(&mut self, name: impl Into<String>) {
+        self.breakpoints.insert(name.into());
+    }
+
+    /// Run the interpreter under the debugger,
+    /// reading commands from `input` and writing to `out`.
+    /// Returns the same as [Interpreter::run] would
+    /// (or `Ok` early if told to quit or `input` runs out).
+    pub fn run(&mut self, i: &mut Interpreter,
+               input: &mut impl BufRead, out: &mut impl Write) -> Result<(), Val> {
+        loop {
+            if self.should_stop(i) && !self.prompt(i, input, out).unwrap_or(false) {
+                return Ok(());
+            }
+            // not frame_depth, which stays the same for a tail call
+            let entered = i.frames_entered();
+            if i.run_with_fuel(1)? {
+                let _ = writeln!(out, "Finished.");
+                return Ok(());
+            }
+            if i.frames_entered() != entered {
+                if let Some(name) = frame_name(i) {
+                    if self.breakpoints.contains(name.as_ref()) {
+                        let _ = writeln!(out, "Breakpoint: {name}");
+                        self.mode = Mode::Step;
+                    }
+                }
+            }
+        }
+    }
+
+    fn should_stop(&self, i: &Interpreter) -> bool {
+        match self.mode {
+            Mode::Step => true,
+            Mode::Next(d) => i.frame_depth() <= d,
+            Mode::Out(d) => i.frame_depth() < d,
+            Mode::Continue => false,
+        }
+    }
+
+    // Ok(false) to stop debugging
+    fn prompt(&mut self, i: &mut Interpreter,
+              input: &mut impl BufRead, out: &mut impl Write) -> io::Result<bool> {
+        show_where(i, out)?;
+        loop {
+            write!(out, "debug> ")?;
+            out.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(false);
+            }
+            let mut words = line.split_whitespace();
+            let cmd = words.next().unwrap_or("");
+            let arg = words.next();
+            match (cmd, arg) {
+                ("s" | "step", _) => { self.mode = Mode::Step; return Ok(true); },
+                ("n" | "next", _) => {
+                    self.mode = Mode::Next(i.frame_depth());
+                    return Ok(true);
+                },
+                ("o" | "out", _) => {
+                    self.mode = Mode::Out(i.frame_depth());
+                    return Ok(true);
+                },
+                ("c" | "continue", _) => { self.mode = Mode::Continue; return Ok(true); },
+                ("q" | "quit", _) => return Ok(false),
+                ("b" | "break", Some(name)) => self.add_breakpoint(name),
+                ("d" | "delete", Some(name)) => {
+                    if !self.breakpoints.remove(name) {
+                        writeln!(out, "No breakpoint on {name}")?;
+                    }
+                },
+                ("breakpoints", _) => {
+                    for name in self.breakpoints.iter() {
+                        writeln!(out, "  {name}")?;
+                    }
+                },
+                ("stack", _) => {
+                    for v in i.stack_ref().clone().iter() {
+                        writeln!(out, "  {}", describe(i, v))?;
+                    }
+                },
+                ("bt" | "backtrace", _) => show_backtrace(i, out)?,
+                ("l" | "locals", n) => {
+                    match n.map(str::parse::<usize>).unwrap_or(Ok(0)) {
+                        Ok(n) => show_locals(i, n, out)?,
+                        Err(e) => writeln!(out, "Bad frame number: {e}")?,
+                    }
+                },
+                ("w" | "where", _) => show_where(i, out)?,
+                ("h" | "help", _) => write!(out, "{HELP}")?,
+                ("", _) => {},
+                _ => writeln!(out, "Unknown command (try help): {}", line.trim())?,
+            }
+        }
+    }
+}
+
+fn frame_name(i: &mut Interpreter) -> Option<Symbol> {
+    let u = i.uniques_mut().get_type::<Symbol>();
+    i.frame_meta_ref().get_ref::<Symbol>(&u).cloned()
+}
+
+fn show_where(i: &mut Interpreter, out: &mut impl Write) -> io::Result<()> {
+    let name = frame_name(i).map(String::from).unwrap_or_else(|| "???".into());
+    match i.body_ref().top().cloned() {
+        Some(next) => writeln!(out, "[{}] {} -> {}", i.frame_depth(), name, describe(i, &next)),
+        None => writeln!(out, "[{}] {} (end of frame)", i.frame_depth(), name),
+    }
+}
+
+fn show_backtrace(i: &mut Interpreter, out: &mut impl Write) -> io::Result<()> {
+    let name_u = i.uniques_mut().get_type::<Symbol>();
+    let span_u = i.uniques_mut().get_type::<SourceSpan>();
+    for (n, m) in i.backtrace_meta_refs().enumerate() {
+        write!(out, "  {n}: ")?;
+        match m.get_ref::<Symbol>(&name_u) {
+            Some(name) => write!(out, "{name}")?,
+            None => write!(out, "???")?,
+        }
+        if let Some(span) = m.get_ref::<SourceSpan>(&span_u) {
+            write!(out, " ({span})")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn show_locals(i: &mut Interpreter, n: usize, out: &mut impl Write) -> io::Result<()> {
+    let locals = i.stack_defenv_refs().nth(n)
+        .map(|defs| defs.locals_iter().map(|(k, v)| (k.to_string(), v.clone())).collect::<Vec<_>>());
+    match locals {
+        Some(mut locals) => {
+            locals.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, v) in locals {
+                writeln!(out, "  {} = {}", name, describe(i, &v))?;
+            }
+        },
+        None => writeln!(out, "No frame {n}")?,
+    }
+    Ok(())
+}
+
+// Short single-line description of a value.
+fn describe(i: &mut Interpreter, v: &Val) -> String {
+    Printer::new(i.uniques_mut()).print(v)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn debug(i: &mut Interpreter, commands: &str) -> String {
+        let mut out = vec![];
+        assert!(Debugger::default().run(i, &mut commands.as_bytes(), &mut out).is_ok());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn breakpoint_and_locals() {
+        let mut i = Interpreter::new(vec![
+            1.into(), "thing".to_symbol().into(), 3.into(),
+        ]);
+        let mut thing = Val::from(List::from(vec![ 2.into() ]));
+        let u = i.uniques_mut().get_type::<Symbol>();
+        thing.meta_mut().insert_val(u, "thing".to_symbol().into());
+        i.define("thing", thing);
+        let out = debug(&mut i, "b thing\nc\nbt\nl 1\nc\n");
+        assert!(out.contains("Breakpoint: thing"));
+        assert!(out.contains("[1] thing -> 2"));
+        assert!(out.contains("  0: thing\n  1: ???\n"));
+        assert!(out.contains("  thing = (2)"));
+        assert!(out.ends_with("Finished.\n"));
+        assert_eq!(i.stack_ref().len(), 3);
+    }
+
+    #[test]
+    fn breakpoint_tail_calls() {
+        // thing [ again ], again => n-1 thing if n > 0, reusing thing's frame
+        let mut i = Interpreter::new(vec![ 2.into(), "thing".to_symbol().into() ]);
+        i.add_builtin("again", |i: &mut Interpreter| {
+            let n = i.stack_pop::<i64>()?.into_inner();
+            if n > 0 {
+                i.stack_push(n - 1);
+                let thing = i.stack_defenv_refs()
+                    .find_map(|d| d.lookup("thing")).cloned().expect("thing");
+                i.eval_next(thing)?;
+            }
+            Ok(())
+        });
+        let mut thing = Val::from(List::from(vec![ "again".to_symbol().into() ]));
+        let u = i.uniques_mut().get_type::<Symbol>();
+        thing.meta_mut().insert_val(u, "thing".to_symbol().into());
+        i.define("thing", thing);
+        let out = debug(&mut i, "b thing\nc\nc\nc\nc\n");
+        assert_eq!(out.matches("Breakpoint: thing").count(), 3);
+        assert!(out.contains("[1] thing -> again"));
+        assert!(out.ends_with("Finished.\n"));
+    }
+
+    #[test]
+    fn step_next_quit() {
+        let mut i = Interpreter::new(vec![ 1.into(), 2.into(), 3.into() ]);
+        let out = debug(&mut i, "s\nn\nstack\nq\n");
+        assert!(out.contains("[0] ??? -> 3"));
+        assert!(out.contains("  2\n  1\n"));
+        assert_eq!(i.stack_ref().len(), 2);
+        assert!(!i.is_complete());
+    }
+}
+
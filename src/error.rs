@@ -0,0 +1,63 @@
+//! [WorstError]: what [Interpreter::run](crate::interpreter::Interpreter::run)
+//! returns when something goes wrong.
+
+use crate::base::*;
+use crate::reader::SourceSpan;
+
+/// A structured error value, raised with
+/// [Interpreter::error](crate::interpreter::Interpreter::error)
+/// or [Interpreter::raise](crate::interpreter::Interpreter::raise).
+/// It remembers the stack frames it was raised from,
+/// so a backtrace can be shown long after the fact.
+#[derive(Clone)]
+pub struct WorstError {
+    kind: Symbol,
+    payload: Val,
+    frames: Vec<Meta>,
+    source: Option<SourceSpan>,
+}
+impl Value for WorstError {}
+
+/// One entry of [WorstError::backtrace]: the definition name and
+/// where its body was read from, if either is known.
+pub type BacktraceFrame<'a> = (Option<&'a Symbol>, Option<&'a SourceSpan>);
+
+impl WorstError {
+    /// Create an error of the given kind.
+    /// `frames` is the [Meta] of each stack frame, innermost first
+    /// (see [backtrace_meta_refs](crate::interpreter::Interpreter::backtrace_meta_refs)).
+    /// `uniques` is used to find a [SourceSpan] for the payload
+    /// or, failing that, the innermost frame that has one.
+    pub fn new(kind: impl Into<Symbol>, payload: impl Into<Val>,
+               frames: Vec<Meta>, uniques: &UniqueGen) -> Self {
+        let payload = payload.into();
+        let source = uniques.lookup_type::<SourceSpan>().and_then(|u| {
+            let get = |m: &Meta| m.get_ref::<SourceSpan>(&u).cloned();
+            get(payload.meta_ref())
+                .or_else(|| payload.downcast_ref::<List>()
+                         .and_then(|l| l.iter().find_map(|v| get(v.meta_ref()))))
+                .or_else(|| frames.iter().find_map(get))
+        });
+        WorstError { kind: kind.into(), payload, frames, source }
+    }
+
+    /// What kind of error this is, e.g. `undefined` or `wrong-type`.
+    pub fn kind(&self) -> &Symbol { &self.kind }
+    /// Whatever else was given when the error was raised.
+    pub fn payload(&self) -> &Val { &self.payload }
+    /// The [Meta] of each stack frame at the time of the error, innermost first.
+    pub fn frames(&self) -> &[Meta] { &self.frames }
+    /// The best guess at where in the source code the error happened.
+    pub fn source(&self) -> Option<&SourceSpan> { self.source.as_ref() }
+
+    /// The name and source location of each frame, innermost first.
+    pub fn backtrace<'a>(&'a self, uniques: &UniqueGen) -> impl Iterator<Item=BacktraceFrame<'a>> {
+        let name = uniques.lookup_type::<Symbol>();
+        let span = uniques.lookup_type::<SourceSpan>();
+        self.frames.iter().map(move |m| (
+            name.as_ref().and_then(|u| m.get_ref::<Symbol>(u)),
+            span.as_ref().and_then(|u| m.get_ref::<SourceSpan>(u)),
+        ))
+    }
+}
+
@@ -4,11 +4,14 @@
 use std::rc::Rc;
 use im_rc::{HashMap, HashSet};
 use crate::base::*;
+use crate::error::WorstError;
 use std::any::TypeId;
 
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+pub mod snapshot;
+
 #[derive(Default, Clone)]
 struct DefEnvEntry {
     local: Option<Val>,
@@ -18,21 +21,24 @@ struct DefEnvEntry {
 /// An environment of definitions for a stack frame.
 #[derive(Default, Clone)]
 pub struct DefEnv {
-    locals: HashSet<String>,
-    entries: HashMap<String, DefEnvEntry>,
+    locals: HashSet<Symbol>,
+    entries: HashMap<Symbol, DefEnvEntry>,
 }
 impl Value for DefEnv {}
 
 impl DefEnv {
     /// Look up a definition.
-    pub fn lookup(&self, key: &str) -> Option<&Val> {
-        self.entries.get(key).and_then(|s| s.local.as_ref().or(s.ambient.as_ref()))
+    /// Give it a [Symbol] (or `&Symbol`) where possible,
+    /// as a string has to be interned first.
+    pub fn lookup(&self, key: impl Into<Symbol>) -> Option<&Val> {
+        self.entries.get(&key.into()).and_then(|s| s.local.as_ref().or(s.ambient.as_ref()))
     }
 
     /// Look up a definition in locals only.
-    pub fn get_local(&self, key: &str) -> Option<&Val> {
-        if self.locals.contains(key) {
-            self.entries.get(key).and_then(|s| s.local.as_ref())
+    pub fn get_local(&self, key: impl Into<Symbol>) -> Option<&Val> {
+        let key = key.into();
+        if self.locals.contains(&key) {
+            self.entries.get(&key).and_then(|s| s.local.as_ref())
         } else { None }
     }
 
@@ -43,37 +49,38 @@ impl DefEnv {
     }
 
     /// Insert a new local value.
-    pub fn insert_local(&mut self, key: String, val: Val) {
+    pub fn insert_local(&mut self, key: impl Into<Symbol>, val: Val) {
+        let key = key.into();
         if self.locals.insert(key.clone()).is_none() {
             // new local, perhaps
-            let entry = self.entries.entry(key).or_insert_with(DefEnvEntry::default);
+            let entry = self.entries.entry(key).or_default();
             // after new_locals, entry.local is now actually ambient
             entry.ambient = entry.local.take();
             entry.local = Some(val);
         } else {
             // overwriting existing local
-            let entry = self.entries.entry(key).or_insert_with(DefEnvEntry::default);
+            let entry = self.entries.entry(key).or_default();
             entry.local = Some(val);
         }
     }
 
     /// Get an iterator over the local definitions
-    pub fn locals_iter(&self) -> impl Iterator<Item=(&str, &Val)> {
+    pub fn locals_iter(&self) -> impl Iterator<Item=(&Symbol, &Val)> {
         self.locals.iter().filter_map(|k| {
             self.entries.get(k)
                 .and_then(|e| e.local.as_ref())
-                .map(|l| (k.as_ref(), l))
+                .map(|l| (k, l))
         })
     }
 
     /// Get an iterator over all definitions
     /// (returning also whether each definition was local or not)
-    pub fn iter(&self) -> impl Iterator<Item=(&str, &Val, bool)> {
+    pub fn iter(&self) -> impl Iterator<Item=(&Symbol, &Val, bool)> {
         self.entries.iter().filter_map(|(k, e)| {
             if e.local.is_some() {
-                e.local.as_ref().map(|v| (k.as_ref(), v, true))
+                e.local.as_ref().map(|v| (k, v, true))
             } else {
-                e.ambient.as_ref().map(|v| (k.as_ref(), v, false))
+                e.ambient.as_ref().map(|v| (k, v, false))
             }
         })
     }
@@ -81,9 +88,9 @@ impl DefEnv {
     /// Copy local definitions into self's local definitions.
     pub fn extend_locals(&mut self, locals: DefEnv) {
         for (l, def) in locals.locals_iter() {
-            let l = String::from(l);
+            let l = l.clone();
             self.locals.insert(l.clone());
-            let entry = self.entries.entry(l).or_insert_with(DefEnvEntry::default);
+            let entry = self.entries.entry(l).or_default();
             entry.local = Some(def.clone());
         }
     }
@@ -101,6 +108,17 @@ impl Frame {
     fn is_empty(&self) -> bool {
         self.childs.is_empty() && self.body.is_empty()
     }
+    // Whether both frames come from evaluating the same (non-anonymous) thing,
+    // so would look the same from inside except for their definitions.
+    // A frame's meta is a clone of the meta of the list it came from,
+    // and clones of a value share its meta until it is changed,
+    // so the same meta means the same list (e.g. one definition called again).
+    // Changing meta (like attaching a defenv) makes a new one, so it won't
+    // match code that merely looks the same. Lists without any meta
+    // (e.g. made while running) have nothing to tell them apart, so never count.
+    fn is_same_call(&self, other: &Frame) -> bool {
+        !self.meta.is_empty() && self.meta.ptr_eq(&other.meta)
+    }
     // TODO clean up
     fn from_list_env(body: List, meta: Meta, defs: DefEnv) -> Self {
         let childs = vec![];
@@ -110,8 +128,13 @@ impl Frame {
 
 enum ChildFrame {
     Frame(Frame),
-    Builtin(Builtin),
+    // a frame left by enter_parent_frame, to carry on with later
+    Resume(Frame),
+    // always a Builtin, kept whole so snapshots can find its name
+    Builtin(Val),
     Once(Box<dyn FnOnce(&mut Interpreter) -> BuiltinRet>),
+    // see eval_next_catch
+    Catch,
 }
 
 /// A Worst interpreter.
@@ -122,6 +145,9 @@ pub struct Interpreter {
     parents: Vec<Frame>,
     stack: List,
     uniques: UniqueGen,
+    max_frame_depth: Option<usize>,
+    max_stack_length: Option<usize>,
+    frames_entered: usize,
 }
 
 /// Return type for [Builtin] functions.
@@ -137,6 +163,19 @@ impl<T: 'static + Fn(&mut Interpreter) -> BuiltinRet> From<T> for Builtin {
     }
 }
 
+fn constant_builtin(v: Val) -> Builtin {
+    Builtin::from(move |i: &mut Interpreter| {
+        i.stack_push(v.clone());
+        Ok(())
+    })
+}
+
+/// Meta entry on a [Builtin] made by [Interpreter::make_constant],
+/// remembering the value it puts on the stack.
+#[derive(Clone)]
+pub struct ConstantValue(pub Val);
+impl Value for ConstantValue {}
+
 impl Interpreter {
 
     /// Create a new "inner" interpreter that is otherwise empty.
@@ -168,30 +207,128 @@ impl Interpreter {
     /// Run until the next pause or error, or to completion.
     pub fn run(&mut self) -> Result<(), Val> {
         loop {
-            if let Some(child) = self.frame.childs.pop() {
-                match child {
-                    ChildFrame::Builtin(b) => {
-                        b.0(self)?
-                    },
-                    ChildFrame::Once(f) => f(self)?,
-                    ChildFrame::Frame(mut f) => {
-                        std::mem::swap(&mut self.frame, &mut f);
-                        self.parents.push(f);
-                    },
-                }
-            } else if let Some(next) = self.frame.body.pop() {
-                // everything except symbols is literal
-                if let Some(s) = next.downcast_ref::<Symbol>() {
-                    self.eval_next_resolve(s)?;
-                } else {
-                    self.stack_push(next);
-                }
-            } else if let Some(mut frame) = self.parents.pop() {
-                std::mem::swap(&mut self.frame, &mut frame);
+            match self.step() {
+                Ok(true) => return Ok(()),
+                Ok(false) => {},
+                Err(e) => self.catch(e)?,
+            }
+        }
+    }
+
+    /// Same as [run], but give up after `fuel` steps,
+    /// where a step is evaluating one value or entering or leaving a frame.
+    /// Returns `Ok(true)` on completion, or `Ok(false)` when out of fuel,
+    /// in which case calling [run] or [run_with_fuel] again
+    /// will carry on where it left off.
+    pub fn run_with_fuel(&mut self, fuel: usize) -> Result<bool, Val> {
+        for _ in 0..fuel {
+            match self.step() {
+                Ok(true) => return Ok(true),
+                Ok(false) => {},
+                Err(e) => self.catch(e)?,
+            }
+        }
+        Ok(self.is_complete())
+    }
+
+    // Do the next thing. Ok(true) means there was nothing left to do.
+    fn step(&mut self) -> Result<bool, Val> {
+        let stack_length = self.stack.len();
+        if let Some(child) = self.frame.childs.pop() {
+            match child {
+                ChildFrame::Builtin(b) => {
+                    let b = b.downcast_ref::<Builtin>().expect("ChildFrame::Builtin");
+                    b.0(self)?
+                },
+                ChildFrame::Once(f) => f(self)?,
+                ChildFrame::Frame(f) => self.enter_frame(f)?,
+                // carrying on after enter_parent_frame, so never a tail call
+                ChildFrame::Resume(mut f) => {
+                    std::mem::swap(&mut self.frame, &mut f);
+                    self.parents.push(f);
+                },
+                // made it through without an error
+                ChildFrame::Catch => self.stack_push(false),
+            }
+        } else if let Some(next) = self.frame.body.pop() {
+            // everything except symbols is literal
+            if next.is::<Symbol>() {
+                self.eval_next_resolve(&next)?;
             } else {
+                self.stack_push(next);
+            }
+        } else if let Some(mut frame) = self.parents.pop() {
+            std::mem::swap(&mut self.frame, &mut frame);
+        } else {
+            return Ok(true);
+        }
+        // only when it grew, so a catch handler can still clean up
+        if let Some(max) = self.max_stack_length {
+            if self.stack.len() > max && self.stack.len() > stack_length {
+                return Err(self.make_error("stack-overflow", max as i64));
+            }
+        }
+        Ok(false)
+    }
+
+    // Enter a new frame, but if it's a tail call to something
+    // that's already running (every frame since has nothing left to do),
+    // replace the old call instead, so recursive loops run in constant space.
+    // The frames in between can only be seen through uplevel and are done,
+    // and the old call looks the same as the new one from inside,
+    // so this is only visible in the definitions it had made.
+    // The toplevel frame is always kept.
+    fn enter_frame(&mut self, f: Frame) -> BuiltinRet {
+        let old = std::mem::replace(&mut self.frame, f);
+        if old.is_empty() && !self.parents.is_empty() {
+            if old.is_same_call(&self.frame) {
+                self.frames_entered = self.frames_entered.wrapping_add(1);
                 return Ok(());
             }
+            for idx in (1..self.parents.len()).rev() {
+                if !self.parents[idx].is_empty() {
+                    break;
+                }
+                if self.parents[idx].is_same_call(&self.frame) {
+                    self.parents.truncate(idx);
+                    self.frames_entered = self.frames_entered.wrapping_add(1);
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(max) = self.max_frame_depth {
+            if self.parents.len() >= max {
+                // back out, so a catch point in the old frame can be found
+                self.frame = old;
+                return self.raise("frame-depth-exceeded", max as i64);
+            }
+        }
+        self.parents.push(old);
+        self.frames_entered = self.frames_entered.wrapping_add(1);
+        Ok(())
+    }
+
+    // If e is a WorstError and there is a catch point (see eval_next_catch),
+    // throw away every frame above it and put e on the stack.
+    // Otherwise give e back, leaving every frame as it was
+    // so the host can see where it happened.
+    fn catch(&mut self, e: Val) -> BuiltinRet {
+        if !e.is::<WorstError>() {
+            return Err(e);
+        }
+        let catch_in = |f: &Frame| f.childs.iter()
+            .rposition(|c| matches!(c, ChildFrame::Catch));
+        if catch_in(&self.frame).is_none() {
+            let Some(idx) = self.parents.iter().rposition(|f| catch_in(f).is_some()) else {
+                return Err(e);
+            };
+            self.parents.truncate(idx + 1);
+            self.frame = self.parents.pop().expect("catching frame");
         }
+        let idx = catch_in(&self.frame).expect("catch point");
+        self.frame.childs.truncate(idx);
+        self.stack_push(e);
+        Ok(())
     }
 
     /// Evaluate this thing in the next [run] step.
@@ -199,10 +336,10 @@ impl Interpreter {
     /// If it's is a list, it should already have a ambients attached.
     pub fn eval_next(&mut self, v: impl Into<Val>) -> BuiltinRet {
         let v = v.into();
-        if let Some(s) = v.downcast_ref::<Symbol>() {
-            self.eval_next_resolve(s)?;
-        } else if let Some(b) = v.downcast_ref::<Builtin>() {
-            self.frame.childs.push(ChildFrame::Builtin(b.clone()));
+        if v.is::<Symbol>() {
+            self.eval_next_resolve(&v)?;
+        } else if v.is::<Builtin>() {
+            self.frame.childs.push(ChildFrame::Builtin(v));
         } else if v.is::<List>() {
             let meta = v.meta_ref().clone();
             let l = v.try_downcast::<List>().ok().unwrap();
@@ -245,37 +382,60 @@ impl Interpreter {
         Ok(())
     }
 
+    /// Same as [eval_any_next], but with a catch point:
+    /// if evaluating `v` raises a [WorstError],
+    /// every stack frame entered since is discarded
+    /// and the error is put on the stack instead of leaving [run].
+    /// If it finishes without error, `false` is put on the stack.
+    /// The stack itself is left as it was at the time of the error.
+    pub fn eval_next_catch(&mut self, v: Val) -> BuiltinRet {
+        self.frame.childs.push(ChildFrame::Catch);
+        self.eval_any_next(v)
+    }
+
     /// Find a definition in the current local and then closure environments.
-    pub fn resolve_definition(&self, name: &str) -> Option<&Val> {
+    pub fn resolve_definition(&self, name: impl Into<Symbol>) -> Option<&Val> {
         self.frame.defs.lookup(name)
     }
 
-    fn eval_next_resolve(&mut self, v: &Symbol) -> BuiltinRet {
-        if let Some(def) = self.resolve_definition(v.as_ref()) {
+    // v must be a Symbol; it is kept whole so an error retains its meta
+    fn eval_next_resolve(&mut self, v: &Val) -> BuiltinRet {
+        let s = v.downcast_ref::<Symbol>().expect("eval_next_resolve symbol");
+        if let Some(def) = self.resolve_definition(s) {
             self.eval_next(def.clone())?;
         } else {
-            self.error(List::from(vec!["undefined".to_symbol().into(),
-                                       v.clone().into()]))?;
+            self.raise("undefined", v.clone())?;
         }
         Ok(())
     }
 
     /// Add a definition to the current stack frame.
     /// Inserts meta values such as name and a static environment.
-    pub fn define(&mut self, name: impl Into<String>, def: impl Into<Val>) {
+    pub fn define(&mut self, name: impl Into<Symbol>, def: impl Into<Val>) {
         let name = name.into();
         let def = self.add_meta_type(def.into(), self.defenv_ref().clone());
         self.frame.defs.insert_local(name, def);
     }
 
     /// Add the given value to local definitions.
-    pub fn add_definition(&mut self, name: impl Into<String>, def: impl Into<Val>) {
-        self.frame.defs.insert_local(name.into(), def.into());
+    pub fn add_definition(&mut self, name: impl Into<Symbol>, def: impl Into<Val>) {
+        self.frame.defs.insert_local(name, def.into());
     }
 
     /// Add the given builtin to the ambient definition set.
-    pub fn add_builtin(&mut self, name: impl Into<String>, def: impl Into<Builtin>) {
-        self.frame.defs.insert_local(name.into(), Val::from(def.into()));
+    /// The builtin's [Symbol] meta is set to its name.
+    pub fn add_builtin(&mut self, name: impl Into<Symbol>, def: impl Into<Builtin>) {
+        let name = name.into();
+        let def = self.add_meta_type(Val::from(def.into()), name.clone());
+        self.frame.defs.insert_local(name, def);
+    }
+
+    /// Make a [Builtin] that puts a copy of `v` on the stack when evaluated,
+    /// with a [ConstantValue] meta entry containing `v`.
+    pub fn make_constant(&mut self, v: impl Into<Val>) -> Val {
+        let v = v.into();
+        let c = ConstantValue(v.clone());
+        self.add_meta_type(Val::from(constant_builtin(v)), c)
     }
 
     /// Get a reference to the current [DefEnv].
@@ -292,6 +452,53 @@ impl Interpreter {
             .chain(self.parents.iter().map(|p| &p.meta))
     }
 
+    /// Get the Meta of every stack frame, innermost first
+    /// (the current frame, then each parent out to the topmost frame),
+    /// as used for [WorstError] backtraces.
+    pub fn backtrace_meta_refs(&self) -> impl Iterator<Item = &Meta> {
+        vec![&self.frame.meta].into_iter()
+            .chain(self.parents.iter().rev().map(|p| &p.meta))
+    }
+
+    /// Get the [DefEnv] of every stack frame,
+    /// starting from the current one and working up to the topmost frame.
+    pub fn stack_defenv_refs(&self) -> impl Iterator<Item = &DefEnv> {
+        vec![&self.frame.defs].into_iter()
+            .chain(self.parents.iter().rev().map(|p| &p.defs))
+    }
+
+    /// Get the number of stack frames above the current one.
+    pub fn frame_depth(&self) -> usize { self.parents.len() }
+
+    /// Get how many frames have been entered so far (wrapping around).
+    /// Unlike [frame_depth](Interpreter::frame_depth) this changes
+    /// even when a tail call reuses the current frame,
+    /// so it shows when a new call has started.
+    pub fn frames_entered(&self) -> usize { self.frames_entered }
+
+    /// Get the most stack frames there can be above the toplevel one,
+    /// if limited (see [set_max_frame_depth](Interpreter::set_max_frame_depth)).
+    pub fn max_frame_depth(&self) -> Option<usize> { self.max_frame_depth }
+    /// Limit how many stack frames there can be above the toplevel one,
+    /// or `None` for no limit (the default).
+    /// Entering a frame beyond the limit raises `frame-depth-exceeded`
+    /// with the limit as payload, so runaway recursion can be caught.
+    pub fn set_max_frame_depth(&mut self, max: Option<usize>) {
+        self.max_frame_depth = max;
+    }
+
+    /// Get the longest the stack can be, if limited
+    /// (see [set_max_stack_length](Interpreter::set_max_stack_length)).
+    pub fn max_stack_length(&self) -> Option<usize> { self.max_stack_length }
+    /// Limit how many values can be on the stack,
+    /// or `None` for no limit (the default).
+    /// Going beyond the limit raises `stack-overflow` with the limit as payload.
+    /// The stack is left as it was, and only a step that makes it longer
+    /// raises it again, so whatever catches it has a chance to clean up.
+    pub fn set_max_stack_length(&mut self, max: Option<usize>) {
+        self.max_stack_length = max;
+    }
+
     // maybe all of these should be within List
     // and just have stack_ref and stack_mut
     /// Get a reference to the stack
@@ -341,14 +548,17 @@ impl Interpreter {
     /// Pop the top thing off the stack if it has the given type
     pub fn stack_pop<T: Value>(&mut self) -> BuiltinRet<ValOf<T>> {
         let v = self.stack_pop_val()?;
-        v.try_downcast::<T>().map_err(|v| {
-            let vty = v.val_type_id();
-            self.add_meta_type(List::from(vec![
-                "wrong-type".to_symbol().into(),
-                v, vty.into(), TypeId::of::<T>().into(),
-                std::any::type_name::<T>().to_string().into(),
-            ]).into(), IsError)
-        })
+        v.try_downcast::<T>().map_err(|v| self.wrong_type_error::<T>(v))
+    }
+
+    /// Create the `wrong-type` error for `v` not being a [T],
+    /// as raised by [stack_pop](Interpreter::stack_pop).
+    pub fn wrong_type_error<T: Value>(&mut self, v: Val) -> Val {
+        let vty = v.val_type_id();
+        self.make_error("wrong-type", List::from(vec![
+            v, vty.into(), TypeId::of::<T>().into(),
+            std::any::type_name::<T>().to_string().into(),
+        ]))
     }
 
     /// Get the top thing off the stack without popping it,
@@ -368,14 +578,35 @@ impl Interpreter {
         Err(v.into())
     }
     /// Pause evaluation with an error. [run] will return with this value.
+    /// A [WorstError] is raised as it is (keeping its original backtrace);
+    /// anything else becomes the payload of a new one of kind `error`.
     pub fn error(&mut self, v: impl Into<Val>) -> BuiltinRet {
-        Err(self.add_meta_type(v.into(), IsError))
+        let v = v.into();
+        if v.is::<WorstError>() {
+            Err(self.add_meta_type(v, IsError))
+        } else {
+            self.raise("error", v)
+        }
+    }
+
+    /// Pause evaluation with a new [WorstError] of the given kind.
+    /// [run] will return with it.
+    pub fn raise(&mut self, kind: impl Into<Symbol>, payload: impl Into<Val>) -> BuiltinRet {
+        Err(self.make_error(kind, payload))
+    }
+
+    /// Create a [WorstError] (with [IsError] set) without raising it,
+    /// capturing the current stack frames as its backtrace.
+    pub fn make_error(&mut self, kind: impl Into<Symbol>, payload: impl Into<Val>) -> Val {
+        let frames = self.backtrace_meta_refs().cloned().collect();
+        let e = WorstError::new(kind, payload, frames, &self.uniques);
+        self.add_meta_type(e.into(), IsError)
     }
 
     fn or_err<T>(&mut self, v: Option<T>, err: impl Into<Symbol>) -> BuiltinRet<T> {
         match v {
             Some(v) => Ok(v),
-            None => Err(self.add_meta_type(Val::from(err.into()), IsError)),
+            None => Err(self.make_error(err, List::default())),
         }
     }
 
@@ -384,11 +615,11 @@ impl Interpreter {
         if let Some(mut frame) = self.parents.pop() {
             std::mem::swap(&mut self.frame, &mut frame);
             if !frame.is_empty() {
-                self.frame.childs.push(ChildFrame::Frame(frame));
+                self.frame.childs.push(ChildFrame::Resume(frame));
             }
             Ok(())
         } else {
-            self.error("root-uplevel".to_symbol())
+            self.raise("root-uplevel", List::default())
         }
     }
 
@@ -422,6 +653,26 @@ mod test {
         assert!(Interpreter::default().run().is_ok());
     }
 
+    #[test]
+    fn error_backtrace_order() {
+        let mut i = Interpreter::new(vec![ "outer".to_symbol().into() ]);
+        let u = i.uniques_mut().get_type::<Symbol>();
+        // not tail calls, so each one keeps its frame;
+        // defined innermost first, so each can see the one it calls
+        for (name, body) in [("inner", "nope"), ("middle", "inner"), ("outer", "middle")] {
+            let mut def = Val::from(List::from(vec![ body.to_symbol().into(), 1.into() ]));
+            def.meta_mut().insert_val(u.clone(), name.to_symbol().into());
+            i.define(name, def);
+        }
+        let e = i.run().err().unwrap().try_downcast::<WorstError>().ok().unwrap();
+        let names = e.as_ref().backtrace(i.uniques_mut())
+            .map(|(name, _)| name.map(|n| n.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![
+            Some("inner".into()), Some("middle".into()), Some("outer".into()), None,
+        ]);
+    }
+
     #[test]
     fn interp_simple_1() {
         // stack
@@ -539,11 +790,152 @@ mod test {
             Ok(())
         });
         let err = i.run().unwrap_err();
-        // assert!(IsError::is_error(&err));
-        assert_eq!(err.downcast_ref::<Symbol>(),
+        assert_eq!(err.downcast_ref::<WorstError>().map(WorstError::kind),
                    Some(&"stack-empty".to_symbol()));
     }
 
+    #[test]
+    fn catch_error() {
+        let mut i =
+            Interpreter::new(vec![
+                List::from(vec![ "thing".to_symbol().into() ]).into(),
+                "catch".to_symbol().into(),
+                "after".to_symbol().into(),
+            ]);
+        i.add_builtin("catch", |i: &mut Interpreter| {
+            let body = i.stack_pop_val()?;
+            i.eval_next_catch(body)
+        });
+        i.add_builtin("fail", |i: &mut Interpreter| {
+            i.raise("oops", List::default())
+        });
+        i.add_builtin("after", |i: &mut Interpreter| {
+            i.stack_push("after".to_string());
+            Ok(())
+        });
+        i.define("thing", List::from(vec![
+            1.into(), "fail".to_symbol().into(), 2.into(),
+        ]));
+        assert!(i.run().is_ok());
+        assert_eq!(pop_cast::<String>(&mut i), "after".to_string());
+        let err = i.stack_pop::<WorstError>().map_err(|_| "wrong type").unwrap();
+        assert_eq!(err.as_ref().kind(), &"oops".to_symbol());
+        assert_eq!(pop_cast::<i64>(&mut i), 1);
+        assert!(i.stack_ref().is_empty());
+        assert!(i.is_complete());
+    }
+
+    #[test]
+    fn uncaught_error_keeps_frames() {
+        let mut i = Interpreter::new(vec![ "outer".to_symbol().into(), 4.into() ]);
+        i.define("inner", List::from(vec![ 1.into(), "nope".to_symbol().into(), 2.into() ]));
+        i.define("outer", List::from(vec![ "inner".to_symbol().into(), 3.into() ]));
+        let e = i.run().err().unwrap().try_downcast::<WorstError>().ok().unwrap();
+        assert_eq!(e.as_ref().kind(), &"undefined".to_symbol());
+        // still in inner, with outer and the toplevel above it
+        assert_eq!(i.frame_depth(), 2);
+        assert_eq!(i.body_ref().top().and_then(|v| v.downcast_ref::<i64>()), Some(&2));
+        assert_eq!(pop_cast::<i64>(&mut i), 1);
+        assert!(i.stack_ref().is_empty());
+    }
+
+    #[test]
+    fn fuel() {
+        let mut i = Interpreter::new(vec![
+            1.into(), 2.into(), 3.into(), 4.into(), 5.into(),
+        ]);
+        assert_eq!(i.run_with_fuel(2).ok(), Some(false));
+        assert_eq!(i.stack_ref().len(), 2);
+        assert_eq!(i.run_with_fuel(0).ok(), Some(false));
+        assert_eq!(i.stack_ref().len(), 2);
+        assert_eq!(i.run_with_fuel(3).ok(), Some(true));
+        assert_eq!(i.run_with_fuel(100).ok(), Some(true));
+        assert_eq!(pop_cast::<i64>(&mut i), 5);
+        assert!(i.is_complete());
+    }
+
+    #[test]
+    fn catch_nothing() {
+        let mut i = Interpreter::new(vec![ "catch".to_symbol().into() ]);
+        i.add_builtin("catch", |i: &mut Interpreter| {
+            i.eval_next_catch(List::from(vec![ 5.into() ]).into())
+        });
+        assert!(i.run().is_ok());
+        assert!(!pop_cast::<bool>(&mut i));
+        assert_eq!(pop_cast::<i64>(&mut i), 5);
+    }
+
+    #[test]
+    fn tail_call_loop() {
+        // shaped like while: loop [ again ], again => n-1 [ recur ] if n > 0
+        // with more iterations than the depth limit, so only passes with reuse
+        let mut i = Interpreter::new(vec![ 150.into(), "loop".to_symbol().into() ]);
+        i.add_builtin("recur", |i: &mut Interpreter| {
+            // loop isn't in its own definitions, so find it dynamically
+            let l = i.stack_defenv_refs()
+                .find_map(|d| d.lookup("loop")).cloned().expect("loop");
+            i.eval_next(l)
+        });
+        i.add_builtin("again", |i: &mut Interpreter| {
+            let n = i.stack_pop::<i64>()?.into_inner();
+            if n > 0 {
+                i.stack_push(n - 1);
+                i.eval_list_next(List::from(vec![ "recur".to_symbol().into() ]).into());
+            }
+            Ok(())
+        });
+        i.define("loop", List::from(vec![ "again".to_symbol().into() ]));
+        i.set_max_frame_depth(Some(100));
+        let mut depth = 0;
+        while !i.run_with_fuel(10).unwrap_or_else(|_| panic!("error")) {
+            depth = depth.max(i.frame_depth());
+        }
+        assert!(depth <= 3);
+        assert!(i.stack_ref().is_empty());
+    }
+
+    #[test]
+    fn frame_depth_limit() {
+        let mut i =
+            Interpreter::new(vec![
+                List::from(vec![ "thing".to_symbol().into() ]).into(),
+                "catch".to_symbol().into(),
+            ]);
+        i.add_builtin("catch", |i: &mut Interpreter| {
+            let body = i.stack_pop_val()?;
+            i.eval_next_catch(body)
+        });
+        // not a tail call, so it would go on forever
+        i.add_builtin("recur", |i: &mut Interpreter| {
+            let l = i.stack_defenv_refs()
+                .find_map(|d| d.lookup("thing")).cloned().expect("thing");
+            i.eval_next(l)
+        });
+        i.define("thing", List::from(vec![ "recur".to_symbol().into(), 1.into() ]));
+        i.set_max_frame_depth(Some(100));
+        assert!(i.run().is_ok());
+        let err = i.stack_pop::<WorstError>().map_err(|_| "wrong type").unwrap();
+        assert_eq!(err.as_ref().kind(), &"frame-depth-exceeded".to_symbol());
+        assert!(i.stack_ref().is_empty());
+        assert!(i.is_complete());
+    }
+
+    #[test]
+    fn stack_length_limit() {
+        let mut i = Interpreter::new(vec![ 1.into(), 2.into(), 3.into(), 4.into() ]);
+        i.set_max_stack_length(Some(2));
+        assert_eq!(i.run_with_fuel(2).ok(), Some(false));
+        let err = i.run().err().unwrap().try_downcast::<WorstError>().ok().unwrap();
+        assert_eq!(err.as_ref().kind(), &"stack-overflow".to_symbol());
+        assert_eq!(i.stack_ref().len(), 3);
+        // room again
+        i.stack_mut().pop();
+        i.stack_mut().pop();
+        assert!(i.run().is_ok());
+        assert_eq!(pop_cast::<i64>(&mut i), 4);
+        assert_eq!(pop_cast::<i64>(&mut i), 1);
+    }
+
 }
 
 
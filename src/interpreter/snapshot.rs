@@ -0,0 +1,841 @@
+//! Saving the entire state of an [Interpreter] as bytes and restoring it.
+//!
+//! Only some kinds of value can be saved:
+//! [bool], [i64], [BigInt], [BigRational], [f64], [String], [Symbol], [List], bytevectors,
+//! [DefEnv], [Place] (including cycles), [Unique], [TypeId],
+//! i64maps, hashmaps, hashsets, [IsError], [SourceSpan] and [Brackets], along with their [Meta].
+//! A [Builtin] is saved by name (its [Symbol] meta, see
+//! [add_builtin](Interpreter::add_builtin)) and found again by that name
+//! when restoring, unless it is a constant
+//! (see [make_constant](Interpreter::make_constant)),
+//! in which case the constant value itself is saved.
+//! Anything else gives a [SnapshotError::NotSerializable].
+//!
+//! Values shared between several places are saved once
+//! and are still shared after restoring.
+
+use std::any::TypeId;
+use std::collections::HashMap as StdHashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use crate::base::*;
+use crate::builtins::compare::ValueKey;
+use crate::builtins::hashmap::{HashMap, HashSet};
+use crate::builtins::i64map::I64Map;
+use crate::error::WorstError;
+use crate::reader::{ Brackets, Position, SourceSpan };
+use super::*;
+
+const MAGIC: &[u8] = b"WRSTSNAP";
+const VERSION: u64 = 5;
+
+// Value payloads
+const FALSE: u8 = 0;
+const TRUE: u8 = 1;
+const I64: u8 = 2;
+const F64: u8 = 3;
+const STRING: u8 = 4;
+const SYMBOL: u8 = 5;
+const LIST: u8 = 6;
+const BYTEVECTOR: u8 = 7;
+const DEFENV: u8 = 8;
+const PLACE: u8 = 9;
+const UNIQUE: u8 = 10;
+const TYPE_ID: u8 = 11;
+const TYPE_ID_NAMED: u8 = 12;
+const BUILTIN: u8 = 13;
+const CONSTANT: u8 = 14;
+const CONSTANT_VALUE: u8 = 15;
+const IS_ERROR: u8 = 16;
+const SOURCE_SPAN: u8 = 17;
+const I64MAP: u8 = 18;
+const BRACKETS: u8 = 19;
+const BIGINT: u8 = 20;
+const RATIONAL: u8 = 21;
+const HASHMAP: u8 = 22;
+const HASHSET: u8 = 23;
+
+// Uniques
+const TYPE_UNIQUE: u8 = 0;
+const PLAIN_UNIQUE: u8 = 1;
+
+// Child frames
+const CHILD_FRAME: u8 = 0;
+const CHILD_BUILTIN: u8 = 1;
+const CHILD_CATCH: u8 = 2;
+const CHILD_RESUME: u8 = 3;
+
+/// Why a snapshot couldn't be made or restored.
+#[derive(Debug, Clone)]
+pub enum SnapshotError {
+    /// Found something that can't be saved.
+    NotSerializable(String),
+    /// A builtin or type named in the snapshot isn't defined.
+    NotFound(String),
+    /// The data isn't a snapshot, or is damaged or from another version.
+    Malformed(String),
+}
+impl Value for SnapshotError {}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SnapshotError::NotSerializable(s) => write!(f, "not serializable: {s}"),
+            SnapshotError::NotFound(s) => write!(f, "not found: {s}"),
+            SnapshotError::Malformed(s) => write!(f, "malformed snapshot: {s}"),
+        }
+    }
+}
+
+// Types whose type-uniques and TypeIds can be saved, by name.
+fn known_types() -> [(&'static str, TypeId); 22] {
+    [
+        ("<bool>", TypeId::of::<bool>()),
+        ("<i64>", TypeId::of::<i64>()),
+        ("<bigint>", TypeId::of::<BigInt>()),
+        ("<rational>", TypeId::of::<BigRational>()),
+        ("<f64>", TypeId::of::<f64>()),
+        ("<string>", TypeId::of::<String>()),
+        ("<symbol>", TypeId::of::<Symbol>()),
+        ("<list>", TypeId::of::<List>()),
+        ("<bytevector>", TypeId::of::<Vec<u8>>()),
+        ("<defenv>", TypeId::of::<DefEnv>()),
+        ("<place>", TypeId::of::<Place>()),
+        ("<unique>", TypeId::of::<Unique>()),
+        ("<type-id>", TypeId::of::<TypeId>()),
+        ("<builtin>", TypeId::of::<Builtin>()),
+        ("<is-error>", TypeId::of::<IsError>()),
+        ("<source-span>", TypeId::of::<SourceSpan>()),
+        ("<brackets>", TypeId::of::<Brackets>()),
+        ("<constant-value>", TypeId::of::<ConstantValue>()),
+        ("<error>", TypeId::of::<WorstError>()),
+        ("<i64map>", TypeId::of::<I64Map>()),
+        ("<hashmap>", TypeId::of::<HashMap>()),
+        ("<hashset>", TypeId::of::<HashSet>()),
+    ]
+}
+
+fn known_type_name(ty: TypeId) -> Option<&'static str> {
+    known_types().iter().find(|(_, t)| *t == ty).map(|(name, _)| *name)
+}
+
+fn known_type(name: &str) -> Option<TypeId> {
+    known_types().iter().find(|(n, _)| *n == name).map(|(_, t)| *t)
+}
+
+impl Interpreter {
+    /// Save everything: the stack, definitions,
+    /// and whatever is left to evaluate.
+    /// See [restore_snapshot](Interpreter::restore_snapshot).
+    pub fn snapshot(&self) -> Result<Vec<u8>, SnapshotError> {
+        let mut e = Encoder {
+            out: MAGIC.to_vec(),
+            uniques: &self.uniques,
+            objects: StdHashMap::new(),
+            plain: StdHashMap::new(),
+            places: vec![],
+            names: StdHashMap::new(),
+            last_defenv: None,
+            context: None,
+        };
+        e.uint(VERSION);
+        e.frame(&self.frame)?;
+        e.uint(self.parents.len() as u64);
+        for f in self.parents.iter() {
+            e.frame(f)?;
+        }
+        e.list(&self.stack)?;
+        // place contents go last, so anything they refer to
+        // (including the place itself) is complete before they are restored
+        let mut n = 0;
+        while let Some((p, context)) = e.places.get(n).cloned() {
+            e.context = context;
+            e.val(&p.get())?;
+            n += 1;
+        }
+        Ok(e.out)
+    }
+
+    /// Replace the stack, definitions and everything left to evaluate
+    /// with the contents of a [snapshot](Interpreter::snapshot).
+    /// Builtins and named types are looked up in `names`,
+    /// e.g. [builtins::all_defenv](crate::builtins::all_defenv) for this interpreter.
+    /// If this fails, the interpreter is left as it was.
+    pub fn restore_snapshot(&mut self, data: &[u8], names: &DefEnv) -> Result<(), SnapshotError> {
+        let data = data.strip_prefix(MAGIC)
+            .ok_or_else(|| SnapshotError::Malformed("not a snapshot".into()))?;
+        let mut d = Decoder {
+            data, pos: 0,
+            uniques: self.uniques.clone(),
+            names,
+            objects: vec![],
+            plain: StdHashMap::new(),
+            places: vec![],
+            names_seen: vec![],
+            last_defenv: None,
+        };
+        let version = d.uint()?;
+        if version != VERSION {
+            return Err(SnapshotError::Malformed(format!("unknown version {version}")));
+        }
+        let frame = d.frame()?;
+        let mut parents = vec![];
+        for _ in 0..d.uint()? {
+            parents.push(d.frame()?);
+        }
+        let stack = d.list()?;
+        let mut n = 0;
+        while let Some(mut p) = d.places.get(n).cloned() {
+            p.set(d.val()?);
+            n += 1;
+        }
+        if d.pos != d.data.len() {
+            return Err(SnapshotError::Malformed("trailing data".into()));
+        }
+        self.frame = frame;
+        self.parents = parents;
+        self.stack = stack;
+        Ok(())
+    }
+}
+
+// Same value and meta.
+fn same_val(a: &Val, b: &Val) -> bool {
+    a.ptr_eq(b) && a.meta_ref().len() == b.meta_ref().len()
+        && a.meta_ref().iter().all(|(u, v)| {
+            b.meta_ref().get_val(u).is_some_and(|w| v.ptr_eq(&w))
+        })
+}
+
+fn same_entry(a: &DefEnvEntry, b: &DefEnvEntry) -> bool {
+    let same = |a: &Option<Val>, b: &Option<Val>| match (a, b) {
+        (Some(a), Some(b)) => same_val(a, b),
+        (None, None) => true,
+        _ => false,
+    };
+    same(&a.local, &b.local) && same(&a.ambient, &b.ambient)
+}
+
+struct Encoder<'a> {
+    out: Vec<u8>,
+    uniques: &'a UniqueGen,
+    // address -> index, in order of first appearance
+    objects: StdHashMap<usize, u64>,
+    plain: StdHashMap<Unique, u64>,
+    // in order of first appearance, with context
+    places: Vec<(Place, Option<String>)>,
+    names: StdHashMap<Rc<str>, u64>,
+    last_defenv: Option<DefEnv>,
+    // name of the definition being saved, for errors
+    context: Option<String>,
+}
+
+impl Encoder<'_> {
+    fn byte(&mut self, b: u8) { self.out.push(b); }
+
+    // LEB128
+    fn uint(&mut self, mut n: u64) {
+        loop {
+            let b = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.byte(b);
+                return;
+            }
+            self.byte(b | 0x80);
+        }
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.uint(b.len() as u64);
+        self.out.extend_from_slice(b);
+    }
+
+    fn str(&mut self, s: &str) { self.bytes(s.as_bytes()); }
+
+    // Definition and source names repeat a lot:
+    // 0 then the name the first time, then 1 + its index.
+    fn name(&mut self, s: &str) {
+        match self.names.get(s) {
+            Some(idx) => self.uint(idx + 1),
+            None => {
+                let idx = self.names.len() as u64;
+                self.names.insert(Rc::from(s), idx);
+                self.uint(0);
+                self.str(s);
+            },
+        }
+    }
+
+    fn not_serializable(&self, what: &str) -> SnapshotError {
+        SnapshotError::NotSerializable(match &self.context {
+            Some(name) => format!("{what} (in definition {name})"),
+            None => what.to_string(),
+        })
+    }
+
+    fn unique(&mut self, u: &Unique) -> Result<(), SnapshotError> {
+        if u.is_type() {
+            let name = known_types().iter()
+                .find(|(_, t)| self.uniques.lookup_type_id(*t).as_ref() == Some(u))
+                .map(|(name, _)| *name)
+                .ok_or_else(|| self.not_serializable("unique for an unknown type"))?;
+            self.byte(TYPE_UNIQUE);
+            self.str(name);
+        } else {
+            let next = self.plain.len() as u64;
+            let id = *self.plain.entry(u.clone()).or_insert(next);
+            self.byte(PLAIN_UNIQUE);
+            self.uint(id);
+        }
+        Ok(())
+    }
+
+    fn meta(&mut self, m: &Meta) -> Result<(), SnapshotError> {
+        self.uint(m.len() as u64);
+        for (u, v) in m.iter() {
+            self.unique(u)?;
+            self.val(&v)?;
+        }
+        Ok(())
+    }
+
+    fn list(&mut self, l: &List) -> Result<(), SnapshotError> {
+        self.uint(l.len() as u64);
+        for v in l.iter() {
+            self.val(v)?;
+        }
+        Ok(())
+    }
+
+    // 0 then the payload if not seen before, otherwise 1 + its index,
+    // then the meta either way
+    fn val(&mut self, v: &Val) -> Result<(), SnapshotError> {
+        let addr = v.downcast_ref::<Place>().map(Place::addr).unwrap_or_else(|| v.addr());
+        if let Some(idx) = self.objects.get(&addr) {
+            self.uint(idx + 1);
+        } else {
+            let idx = self.objects.len() as u64;
+            self.objects.insert(addr, idx);
+            self.uint(0);
+            self.payload(v)?;
+        }
+        self.meta(v.meta_ref())
+    }
+
+    fn payload(&mut self, v: &Val) -> Result<(), SnapshotError> {
+        if let Some(b) = v.downcast_ref::<bool>() {
+            self.byte(if *b { TRUE } else { FALSE });
+        } else if let Some(n) = v.downcast_ref::<i64>() {
+            self.byte(I64);
+            self.out.extend_from_slice(&n.to_le_bytes());
+        } else if let Some(n) = v.downcast_ref::<BigInt>() {
+            self.byte(BIGINT);
+            self.bytes(&n.to_signed_bytes_le());
+        } else if let Some(r) = v.downcast_ref::<BigRational>() {
+            self.byte(RATIONAL);
+            self.bytes(&r.numer().to_signed_bytes_le());
+            self.bytes(&r.denom().to_signed_bytes_le());
+        } else if let Some(n) = v.downcast_ref::<f64>() {
+            self.byte(F64);
+            self.out.extend_from_slice(&n.to_bits().to_le_bytes());
+        } else if let Some(s) = v.downcast_ref::<String>() {
+            self.byte(STRING);
+            self.str(s);
+        } else if let Some(s) = v.downcast_ref::<Symbol>() {
+            self.byte(SYMBOL);
+            self.str(s.as_ref());
+        } else if let Some(l) = v.downcast_ref::<List>() {
+            self.byte(LIST);
+            self.list(l)?;
+        } else if let Some(b) = v.downcast_ref::<Vec<u8>>() {
+            self.byte(BYTEVECTOR);
+            self.bytes(b);
+        } else if let Some(d) = v.downcast_ref::<DefEnv>() {
+            self.byte(DEFENV);
+            self.defenv(d)?;
+        } else if let Some(p) = v.downcast_ref::<Place>() {
+            self.byte(PLACE);
+            self.places.push((p.clone(), self.context.clone()));
+        } else if let Some(u) = v.downcast_ref::<Unique>() {
+            self.byte(UNIQUE);
+            self.unique(u)?;
+        } else if let Some(t) = v.downcast_ref::<TypeId>() {
+            if let Some(name) = known_type_name(*t) {
+                self.byte(TYPE_ID);
+                self.str(name);
+            } else {
+                let name = self.uniques.lookup_type::<String>()
+                    .and_then(|u| v.meta_ref().get_ref::<String>(&u).cloned())
+                    .ok_or_else(|| self.not_serializable("unnamed type"))?;
+                self.byte(TYPE_ID_NAMED);
+                self.str(&name);
+            }
+        } else if v.is::<Builtin>() {
+            self.builtin(v)?;
+        } else if let Some(c) = v.downcast_ref::<ConstantValue>() {
+            self.byte(CONSTANT_VALUE);
+            self.val(&c.0)?;
+        } else if v.is::<IsError>() {
+            self.byte(IS_ERROR);
+        } else if let Some(s) = v.downcast_ref::<SourceSpan>() {
+            self.byte(SOURCE_SPAN);
+            match &s.source {
+                Some(name) => { self.byte(1); self.name(name); },
+                None => self.byte(0),
+            }
+            for p in [s.start, s.end] {
+                self.uint(p.line as u64);
+                self.uint(p.column as u64);
+            }
+        } else if let Some(b) = v.downcast_ref::<Brackets>() {
+            self.byte(BRACKETS);
+            self.byte(b.open() as u8);
+        } else if let Some(m) = v.downcast_ref::<I64Map>() {
+            self.byte(I64MAP);
+            self.uint(m.data.len() as u64);
+            for (k, v) in m.data.iter() {
+                self.out.extend_from_slice(&k.to_le_bytes());
+                self.val(v)?;
+            }
+        } else if let Some(m) = v.downcast_ref::<HashMap>() {
+            self.byte(HASHMAP);
+            self.uint(m.data.len() as u64);
+            for (k, v) in m.data.iter() {
+                self.val(&k.0)?;
+                self.val(v)?;
+            }
+        } else if let Some(s) = v.downcast_ref::<HashSet>() {
+            self.byte(HASHSET);
+            self.uint(s.data.len() as u64);
+            for v in s.data.iter() {
+                self.val(&v.0)?;
+            }
+        } else {
+            let what = if v.is::<WorstError>() { "error value" } else { "native value" };
+            return Err(self.not_serializable(what));
+        }
+        Ok(())
+    }
+
+    fn builtin(&mut self, v: &Val) -> Result<(), SnapshotError> {
+        let constant = self.uniques.lookup_type::<ConstantValue>()
+            .and_then(|u| v.meta_ref().get_ref::<ConstantValue>(&u));
+        if let Some(c) = constant {
+            self.byte(CONSTANT);
+            return self.val(&c.0);
+        }
+        let name = self.uniques.lookup_type::<Symbol>()
+            .and_then(|u| v.meta_ref().get_ref::<Symbol>(&u))
+            .ok_or_else(|| self.not_serializable("anonymous builtin"))?;
+        self.byte(BUILTIN);
+        self.str(name.as_ref());
+        Ok(())
+    }
+
+    fn opt_val(&mut self, v: Option<&Val>) -> Result<(), SnapshotError> {
+        match v {
+            Some(v) => { self.byte(1); self.val(v) },
+            None => { self.byte(0); Ok(()) },
+        }
+    }
+
+    // Saved as the differences from the previous one,
+    // as most are only a few definitions apart.
+    fn defenv(&mut self, d: &DefEnv) -> Result<(), SnapshotError> {
+        let base = self.last_defenv.clone().unwrap_or_default();
+        self.uint(d.locals.len() as u64);
+        for name in d.locals.iter() {
+            self.name(name.as_ref());
+        }
+        let removed = base.entries.keys()
+            .filter(|name| !d.entries.contains_key(*name))
+            .collect::<Vec<_>>();
+        self.uint(removed.len() as u64);
+        for name in removed {
+            self.name(name.as_ref());
+        }
+        let changed = d.entries.iter()
+            .filter(|(name, e)| !base.entries.get(*name).is_some_and(|b| same_entry(b, e)))
+            .collect::<Vec<_>>();
+        self.uint(changed.len() as u64);
+        for (name, entry) in changed {
+            self.name(name.as_ref());
+            let outer = self.context.replace(name.to_string());
+            self.opt_val(entry.local.as_ref())?;
+            self.opt_val(entry.ambient.as_ref())?;
+            self.context = outer;
+        }
+        self.last_defenv = Some(d.clone());
+        Ok(())
+    }
+
+    fn frame(&mut self, f: &Frame) -> Result<(), SnapshotError> {
+        self.uint(f.childs.len() as u64);
+        for c in f.childs.iter() {
+            match c {
+                ChildFrame::Frame(f) => {
+                    self.byte(CHILD_FRAME);
+                    self.frame(f)?;
+                },
+                ChildFrame::Resume(f) => {
+                    self.byte(CHILD_RESUME);
+                    self.frame(f)?;
+                },
+                ChildFrame::Builtin(b) => {
+                    self.byte(CHILD_BUILTIN);
+                    self.val(b)?;
+                },
+                ChildFrame::Catch => self.byte(CHILD_CATCH),
+                ChildFrame::Once(_) => {
+                    return Err(self.not_serializable("builtin in progress"));
+                },
+            }
+        }
+        self.list(&f.body)?;
+        self.defenv(&f.defs)?;
+        self.meta(&f.meta)
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    uniques: UniqueGen,
+    names: &'a DefEnv,
+    // by index, None while being decoded
+    objects: Vec<Option<Val>>,
+    plain: StdHashMap<u64, Unique>,
+    // to be filled in at the end
+    places: Vec<Place>,
+    names_seen: Vec<Rc<str>>,
+    last_defenv: Option<DefEnv>,
+}
+
+fn malformed(what: &str) -> SnapshotError {
+    SnapshotError::Malformed(what.to_string())
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SnapshotError> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.data.len())
+            .ok_or_else(|| malformed("unexpected end"))?;
+        let b = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(b)
+    }
+
+    fn byte(&mut self) -> Result<u8, SnapshotError> { Ok(self.take(1)?[0]) }
+
+    fn uint(&mut self) -> Result<u64, SnapshotError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            n |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(malformed("number too long"))
+    }
+
+    fn len(&mut self) -> Result<usize, SnapshotError> {
+        let n = self.uint()? as usize;
+        // every item takes at least a byte
+        if n > self.data.len() - self.pos {
+            return Err(malformed("length too long"));
+        }
+        Ok(n)
+    }
+
+    fn u64_le(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SnapshotError> {
+        let n = self.len()?;
+        self.take(n)
+    }
+
+    fn str(&mut self) -> Result<String, SnapshotError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| malformed("invalid string"))
+    }
+
+    fn name(&mut self) -> Result<Rc<str>, SnapshotError> {
+        match self.uint()? {
+            0 => {
+                let name = Rc::<str>::from(self.str()?);
+                self.names_seen.push(name.clone());
+                Ok(name)
+            },
+            n => self.names_seen.get(n as usize - 1).cloned()
+                .ok_or_else(|| malformed("bad name")),
+        }
+    }
+
+    fn unique(&mut self) -> Result<Unique, SnapshotError> {
+        match self.byte()? {
+            TYPE_UNIQUE => {
+                let name = self.str()?;
+                let t = known_type(&name)
+                    .ok_or_else(|| SnapshotError::NotFound(format!("type {name}")))?;
+                Ok(self.uniques.get_type_id(t))
+            },
+            PLAIN_UNIQUE => {
+                let id = self.uint()?;
+                let uniques = &mut self.uniques;
+                Ok(self.plain.entry(id).or_insert_with(|| uniques.create()).clone())
+            },
+            _ => Err(malformed("bad unique")),
+        }
+    }
+
+    fn meta(&mut self) -> Result<Meta, SnapshotError> {
+        let mut m = Meta::default();
+        for _ in 0..self.len()? {
+            let u = self.unique()?;
+            let v = self.val()?;
+            m.insert_val(u, v);
+        }
+        Ok(m)
+    }
+
+    fn list(&mut self) -> Result<List, SnapshotError> {
+        let mut l = vec![];
+        for _ in 0..self.len()? {
+            l.push(self.val()?);
+        }
+        Ok(List::from(l))
+    }
+
+    fn val(&mut self) -> Result<Val, SnapshotError> {
+        let r = self.uint()?;
+        let mut v = if r == 0 {
+            let idx = self.objects.len();
+            self.objects.push(None);
+            let v = self.payload()?;
+            self.objects[idx] = Some(v.clone());
+            v
+        } else {
+            self.objects.get((r - 1) as usize).cloned().flatten()
+                .ok_or_else(|| malformed("bad reference"))?
+        };
+        *v.meta_mut() = self.meta()?;
+        Ok(v)
+    }
+
+    fn named(&mut self, what: &str) -> Result<Val, SnapshotError> {
+        let name = self.str()?;
+        self.names.lookup(name.as_str()).cloned()
+            .ok_or_else(|| SnapshotError::NotFound(format!("{what} {name}")))
+    }
+
+    fn payload(&mut self) -> Result<Val, SnapshotError> {
+        Ok(match self.byte()? {
+            FALSE => false.into(),
+            TRUE => true.into(),
+            I64 => (self.u64_le()? as i64).into(),
+            BIGINT => BigInt::from_signed_bytes_le(self.bytes()?).into(),
+            RATIONAL => {
+                let n = BigInt::from_signed_bytes_le(self.bytes()?);
+                let d = BigInt::from_signed_bytes_le(self.bytes()?);
+                if d == BigInt::ZERO { return Err(malformed("zero denominator")); }
+                BigRational::new(n, d).into()
+            },
+            F64 => f64::from_bits(self.u64_le()?).into(),
+            STRING => self.str()?.into(),
+            SYMBOL => Symbol::from(self.str()?).into(),
+            LIST => self.list()?.into(),
+            BYTEVECTOR => self.bytes()?.to_vec().into(),
+            DEFENV => self.defenv()?.into(),
+            PLACE => {
+                let p = Place::wrap(false);
+                self.places.push(p.clone());
+                p.into()
+            },
+            UNIQUE => self.unique()?.into(),
+            TYPE_ID => {
+                let name = self.str()?;
+                known_type(&name)
+                    .ok_or_else(|| SnapshotError::NotFound(format!("type {name}")))?
+                    .into()
+            },
+            TYPE_ID_NAMED => {
+                let t = self.named("type")?;
+                if !t.is::<TypeId>() {
+                    return Err(SnapshotError::NotFound("type (not a type)".into()));
+                }
+                t
+            },
+            BUILTIN => {
+                let b = self.named("builtin")?;
+                if !b.is::<Builtin>() {
+                    return Err(SnapshotError::NotFound("builtin (not a builtin)".into()));
+                }
+                b
+            },
+            CONSTANT => constant_builtin(self.val()?).into(),
+            CONSTANT_VALUE => ConstantValue(self.val()?).into(),
+            IS_ERROR => IsError.into(),
+            SOURCE_SPAN => {
+                let source = match self.byte()? {
+                    0 => None,
+                    _ => Some(self.name()?),
+                };
+                let mut pos = || -> Result<Position, SnapshotError> {
+                    Ok(Position { line: self.uint()? as usize, column: self.uint()? as usize })
+                };
+                let start = pos()?;
+                let end = pos()?;
+                SourceSpan { source, start, end }.into()
+            },
+            BRACKETS => match self.byte()? {
+                b'(' => Brackets::Round.into(),
+                b'[' => Brackets::Square.into(),
+                b'{' => Brackets::Curly.into(),
+                _ => return Err(malformed("unknown brackets")),
+            },
+            I64MAP => {
+                let mut m = I64Map::default();
+                for _ in 0..self.len()? {
+                    let k = self.u64_le()? as i64;
+                    let v = self.val()?;
+                    m.data.insert(k, v);
+                }
+                m.into()
+            },
+            HASHMAP => {
+                let mut m = HashMap::default();
+                for _ in 0..self.len()? {
+                    let k = self.val()?;
+                    let v = self.val()?;
+                    m.data.insert(ValueKey(k), v);
+                }
+                m.into()
+            },
+            HASHSET => {
+                let mut s = HashSet::default();
+                for _ in 0..self.len()? {
+                    let v = self.val()?;
+                    s.data.insert(ValueKey(v));
+                }
+                s.into()
+            },
+            _ => return Err(malformed("unknown value")),
+        })
+    }
+
+    fn opt_val(&mut self) -> Result<Option<Val>, SnapshotError> {
+        match self.byte()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.val()?)),
+        }
+    }
+
+    fn defenv(&mut self) -> Result<DefEnv, SnapshotError> {
+        let mut d = self.last_defenv.clone().unwrap_or_default();
+        d.locals.clear();
+        for _ in 0..self.len()? {
+            d.locals.insert(Symbol::from(self.name()?.as_ref()));
+        }
+        for _ in 0..self.len()? {
+            d.entries.remove(&Symbol::from(self.name()?.as_ref()));
+        }
+        for _ in 0..self.len()? {
+            let name = Symbol::from(self.name()?.as_ref());
+            let local = self.opt_val()?;
+            let ambient = self.opt_val()?;
+            d.entries.insert(name, DefEnvEntry { local, ambient });
+        }
+        self.last_defenv = Some(d.clone());
+        Ok(d)
+    }
+
+    fn frame(&mut self) -> Result<Frame, SnapshotError> {
+        let mut childs = vec![];
+        for _ in 0..self.len()? {
+            childs.push(match self.byte()? {
+                CHILD_FRAME => ChildFrame::Frame(self.frame()?),
+                CHILD_RESUME => ChildFrame::Resume(self.frame()?),
+                CHILD_BUILTIN => {
+                    let b = self.val()?;
+                    if !b.is::<Builtin>() {
+                        return Err(malformed("pending builtin isn't a builtin"));
+                    }
+                    ChildFrame::Builtin(b)
+                },
+                CHILD_CATCH => ChildFrame::Catch,
+                _ => return Err(malformed("unknown child frame")),
+            });
+        }
+        let body = self.list()?;
+        let defs = self.defenv()?;
+        let meta = self.meta()?;
+        Ok(Frame { childs, body, defs, meta })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builtins;
+    use crate::reader::read_all;
+
+    fn interp(src: &str) -> Interpreter {
+        let mut i = Interpreter::default();
+        builtins::install(&mut i);
+        *i.body_mut() = List::from(read_all(&mut src.chars()).ok().unwrap());
+        i
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut i = interp("
+            5 value->constant quote five definition-add
+            #f make-place clone clone place-set drop quote loop definition-add
+            quote thing value->constant quote thing-later definition-add
+            \"hi\" 1.5
+        ");
+        assert!(i.run().is_ok());
+        i.body_mut().push(Val::from("after".to_symbol()));
+        let data = i.snapshot().ok().unwrap();
+
+        let mut j = interp("");
+        let names = builtins::all_defenv(&j);
+        assert!(j.restore_snapshot(&data, &names).is_ok());
+        j.define("after", Builtin::from(|i: &mut Interpreter| {
+            i.eval_next(Val::from("five".to_symbol()))
+        }));
+        assert!(j.run().is_ok());
+        assert_eq!(j.stack_pop::<i64>().ok().unwrap().into_inner(), 5);
+        assert_eq!(j.stack_pop::<f64>().ok().unwrap().into_inner(), 1.5);
+        assert_eq!(j.stack_pop::<String>().ok().unwrap().as_ref(), "hi");
+
+        // a place containing itself is still the same place
+        let p = j.resolve_definition("loop").unwrap().clone();
+        let p = p.downcast_ref::<Place>().unwrap();
+        assert_eq!(p.addr(), p.get().downcast_ref::<Place>().unwrap().addr());
+    }
+
+    #[test]
+    fn not_serializable() {
+        let mut i = interp("reader-empty quote r definition-add");
+        assert!(i.run().is_ok());
+        match i.snapshot() {
+            Err(SnapshotError::NotSerializable(s)) => assert!(s.contains("r)")),
+            _ => panic!("expected NotSerializable"),
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let mut i = interp("");
+        let names = builtins::all_defenv(&i);
+        assert!(matches!(i.restore_snapshot(b"nope", &names), Err(SnapshotError::Malformed(_))));
+        let mut data = i.snapshot().ok().unwrap();
+        data.pop();
+        assert!(matches!(i.restore_snapshot(&data, &names), Err(SnapshotError::Malformed(_))));
+    }
+}
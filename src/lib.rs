@@ -4,8 +4,12 @@
 //! Hello and welcome to my programming language :)
 
 pub mod base;
+pub mod compiled;
+pub mod debugger;
+pub mod error;
 pub mod interpreter;
 pub mod reader;
+pub mod printer;
 pub mod builtins;
 
 #[cfg(feature = "wasm")]
@@ -13,12 +17,15 @@ pub mod wasm;
 
 
 #[cfg(feature = "enable_fs_embed")]
-/// Create an interpreter that runs `worst/prelude.w` from the embedded filesystem.
+/// Create an interpreter that runs `base/prelude.w` from the embedded filesystem.
+/// The prelude only defines things, so add code to the end of the
+/// [body](interpreter::Interpreter::body_mut) to run it afterwards.
 /// Panics if it is missing or malformed.
 pub fn embedded() -> interpreter::Interpreter {
-    let file = builtins::fs::embed::open_read_str("base/prelude.w")
+    let mut i = interpreter::Interpreter::default();
+    let prelude = builtins::fs::embed::read_code("base/prelude.w", i.uniques_mut())
         .expect("embedded worst/prelude.w");
-    let prelude = reader::read_all(&mut file.chars()).unwrap();
-    interpreter::Interpreter::new(prelude)
+    *i.body_mut() = prelude.unwrap();
+    i
 }
 
@@ -1,66 +1,306 @@
 
 use std::process::ExitCode;
-use std::any::TypeId;
+use std::io;
 use worst::interpreter::*;
 use worst::builtins;
 use worst::base::*;
+use worst::debugger::Debugger;
+use worst::error::WorstError;
+use worst::reader::Reader;
+use worst::printer::Printer;
+#[cfg(feature = "enable_os")]
+use worst::builtins::os::Exit;
 
-fn basic_printerr(i: &mut Interpreter, v: &Val) {
-    if let Some(v) = v.downcast_ref::<Symbol>() {
-        eprint!("{v}");
-    } else if let Some(v) = v.downcast_ref::<bool>() {
-        eprint!("{}", if *v { "#t" } else { "#f" });
-    } else if let Some(v) = v.downcast_ref::<String>() {
-        eprint!("{v:?}");
-    } else if let Some(v) = v.downcast_ref::<i64>() {
-        eprint!("{v}");
-    } else if let Some(v) = v.downcast_ref::<f64>() {
-        eprint!("{v}");
-    } else if let Some(v) = v.downcast_ref::<List>() {
-        eprint!("(");
-        for v in v.iter() {
-            basic_printerr(i, v);
-            eprint!(" ");
-        }
-        eprint!(")");
-    } else if v.is::<TypeId>() {
-        let name = i.uniques_mut().get_type::<String>();
-        if let Some(v) = v.meta_ref().get_val(&name) {
-            eprint!("{}", v.downcast_ref::<String>().unwrap());
+fn print_error(i: &mut Interpreter, e: &WorstError) {
+    let printer = Printer::new(i.uniques_mut());
+    eprint!("{}", e.kind());
+    if !e.payload().downcast_ref::<List>().is_some_and(List::is_empty) {
+        eprint!(" ");
+        eprint!("{}", printer.print(e.payload()));
+    }
+    if let Some(span) = e.source() {
+        eprint!("\nAt: {span}");
+    }
+    eprint!("\nStack: ");
+    for v in i.stack_ref().iter() {
+        eprint!("{} ", printer.print(v));
+    }
+    eprintln!("\nCall stack:");
+    for (name, span) in e.backtrace(i.uniques_mut()) {
+        if let Some(name) = name {
+            eprint!("  {name}");
         } else {
-            eprint!("<type>");
+            eprint!("  ???");
+        }
+        if let Some(span) = span {
+            eprint!(" ({span})");
         }
+        eprintln!();
+    }
+}
+
+const USAGE: &str = "\
+Usage: worst [options] [command]
+
+Commands:
+  run <file> [args...]    Run a script
+  <file> [args...]        The same, or run a cli module if there's no such file
+  -e <code> [args...]     Run some code
+  repl                    Start the interactive environment (the default)
+  check <file>...         Read files without running them, to find syntax errors
+
+Options:
+  --lib-path <dir>        Look for modules in dir first (may be repeated)
+  --no-prelude            Only define builtins, without the prelude
+  --features              List the enabled features and exit
+  --debug                 Run under the debugger
+  --max-frame-depth <n>   Raise frame-depth-exceeded beyond n stack frames
+  --max-stack-length <n>  Raise stack-overflow beyond n values on the stack
+  -h, --help              Show this help and exit
+  -V, --version           Show the version and exit
+
+Set WORST_CACHE_DIR to keep compiled copies of imported modules there.
+
+Exits with 1 after an uncaught error and 2 for bad usage.
+Otherwise the status given to exit is used (1 if it's not from 0 to 255),
+or if the program finishes without calling exit,
+the last value left on the stack decides:
+#f or an error value is 1, and anything else (including nothing) is 0.
+Hooks added with exit-hook-add run before exiting either way.";
+
+enum Command {
+    Run { path: String, args: Vec<String> },
+    // a file if it exists, otherwise a cli module
+    RunOrModule { name: String, args: Vec<String> },
+    Eval { code: String, args: Vec<String> },
+    Repl,
+    Check(Vec<String>),
+    Features,
+    Help,
+    Version,
+}
+
+#[derive(Default)]
+struct Options {
+    lib_paths: Vec<String>,
+    no_prelude: bool,
+    debug: bool,
+    max_frame_depth: Option<usize>,
+    max_stack_length: Option<usize>,
+}
+
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<(Options, Command), String> {
+    let mut opts = Options::default();
+    let value = |args: &mut dyn Iterator<Item=String>, name: &str| {
+        args.next().ok_or_else(|| format!("{name} needs a value"))
+    };
+    let limit = |n: String, name: &str| {
+        n.parse::<usize>().map_err(|_| format!("{name} needs a number, not {n:?}"))
+    };
+    while let Some(arg) = args.next() {
+        let cmd = match arg.as_str() {
+            "--lib-path" => { opts.lib_paths.push(value(&mut args, &arg)?); continue; },
+            "--no-prelude" => { opts.no_prelude = true; continue; },
+            "--debug" => { opts.debug = true; continue; },
+            "--max-frame-depth" => {
+                opts.max_frame_depth = Some(limit(value(&mut args, &arg)?, &arg)?);
+                continue;
+            },
+            "--max-stack-length" => {
+                opts.max_stack_length = Some(limit(value(&mut args, &arg)?, &arg)?);
+                continue;
+            },
+            "--features" => Command::Features,
+            "-h" | "--help" => Command::Help,
+            "-V" | "--version" => Command::Version,
+            "-e" => Command::Eval { code: value(&mut args, &arg)?, args: args.collect() },
+            "run" => Command::Run { path: value(&mut args, &arg)?, args: args.collect() },
+            "repl" => Command::Repl,
+            "check" => {
+                let paths = args.collect::<Vec<_>>();
+                if paths.is_empty() {
+                    return Err("check needs at least one file".into());
+                }
+                Command::Check(paths)
+            },
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option {arg}")),
+            _ => Command::RunOrModule { name: arg, args: args.collect() },
+        };
+        return Ok((opts, cmd));
+    }
+    Ok((opts, Command::Repl))
+}
+
+// Read a whole file as code, with its path as the source name.
+fn read_file(path: &str, i: &mut Interpreter) -> Result<Vec<Val>, String> {
+    let src = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    read_code(&src, path, i)
+}
+
+fn read_code(src: &str, name: &str, i: &mut Interpreter) -> Result<Vec<Val>, String> {
+    let mut reader = Reader::new(i.uniques_mut());
+    reader.set_source_name(name);
+    reader.read_all(&mut src.chars()).map_err(|e| format!("{name}: {e:?}"))
+}
+
+fn check(paths: &[String]) -> ExitCode {
+    let mut i = Interpreter::default();
+    let mut ok = true;
+    for path in paths {
+        if let Err(e) = read_file(path, &mut i) {
+            eprintln!("{e}");
+            ok = false;
+        }
+    }
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+// Exit status from whatever is left on top of the stack.
+// Only exit gives other statuses, so a leftover number doesn't count.
+fn stack_exit_status(i: &mut Interpreter) -> u8 {
+    let Some(v) = i.stack_ref().top().cloned() else { return 0 };
+    if v.downcast_ref::<bool>() == Some(&false) {
+        1
+    } else if let Some(e) = v.downcast_ref::<WorstError>() {
+        print_error(i, e);
+        1
     } else {
-        eprint!("(value)");
+        0
     }
 }
 
+// The code to run after the prelude, and what it sees as its arguments.
+fn program(cmd: Command, i: &mut Interpreter) -> Result<(Vec<Val>, Vec<String>), String> {
+    Ok(match cmd {
+        Command::Run { path, args } => {
+            let code = read_file(&path, i)?;
+            let c = i.make_constant(path.clone());
+            i.add_definition("current-script-path", c);
+            (code, [path].into_iter().chain(args).collect())
+        },
+        Command::RunOrModule { name, args } if std::path::Path::new(&name).is_file() => {
+            program(Command::Run { path: name, args }, i)?
+        },
+        Command::RunOrModule { name, args } => {
+            let code = vec![
+                List::from_iter(args.iter().cloned()).into(),
+                "quote".to_symbol().into(), name.as_str().to_symbol().into(),
+                "cli-module-run".to_symbol().into(),
+            ];
+            (code, [name].into_iter().chain(args).collect())
+        },
+        Command::Eval { code, args } => {
+            let code = read_code(&code, "-e", i)?;
+            (code, ["-e".to_string()].into_iter().chain(args).collect())
+        },
+        Command::Repl => (vec!["worst-repl".to_symbol().into()], vec![]),
+        Command::Check(_) | Command::Features | Command::Help | Command::Version => {
+            unreachable!("not a program")
+        },
+    })
+}
+
 fn main() -> ExitCode {
-    let mut i = worst::embedded();
-    builtins::install(&mut i);
-    if let Err(e) = i.run() {
-        // if IsError::is_error(&e) {
-        //     eprint!("\nTop-level error: ");
-        // }
-        basic_printerr(&mut i, &e);
-        eprint!("\nStack: ");
-        for v in i.stack_ref().clone().iter() {
-            basic_printerr(&mut i, v);
-            eprint!(" ");
-        }
-        eprintln!("\nCall stack:");
-        let u = i.uniques_mut().get_type::<Symbol>();
-        let stack = i.stack_meta_refs().map(|m| m.get_val(&u));
-        for name in stack {
-            if let Some(name) = name {
-                eprintln!("  {}", name.downcast_ref::<Symbol>().unwrap());
-            } else {
-                eprintln!("  ???");
+    let mut args = std::env::args();
+    let argv0 = args.next().unwrap_or_else(|| "worst".into());
+    let (opts, cmd) = match parse_args(args) {
+        Ok(r) => r,
+        Err(msg) => {
+            eprintln!("worst: {msg}\n\n{USAGE}");
+            return ExitCode::from(2);
+        },
+    };
+    match &cmd {
+        Command::Help => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        },
+        Command::Version => {
+            println!("worst {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        },
+        Command::Features => {
+            for f in builtins::core::features_enabled() {
+                println!("{f}");
             }
+            return ExitCode::SUCCESS;
+        },
+        Command::Check(paths) => return check(paths),
+        Command::Repl | Command::RunOrModule { .. } if opts.no_prelude => {
+            eprintln!("worst: can't do that with --no-prelude\n\n{USAGE}");
+            return ExitCode::from(2);
+        },
+        _ => {},
+    }
+
+    let mut i = if opts.no_prelude {
+        Interpreter::default()
+    } else {
+        worst::embedded()
+    };
+    builtins::install(&mut i);
+    i.set_max_frame_depth(opts.max_frame_depth);
+    i.set_max_stack_length(opts.max_stack_length);
+
+    let (code, cli_args) = match program(cmd, &mut i) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("worst: {e}");
+            return ExitCode::FAILURE;
+        },
+    };
+    i.add_builtin("command-line-arguments", move |i: &mut Interpreter| {
+        let args = std::iter::once(&argv0).chain(cli_args.iter()).cloned();
+        i.stack_push(List::from_iter(args));
+        Ok(())
+    });
+    // after the prelude: search paths, then the program itself
+    let mut after = vec![];
+    if !opts.no_prelude {
+        for dir in opts.lib_paths.into_iter().rev() {
+            after.push(dir.into());
+            after.push("module-search-path-prepend".to_symbol().into());
         }
-        return ExitCode::FAILURE;
     }
+    after.extend(code);
+    let mut body = List::from(after);
+    body.prepend(std::mem::take(i.body_mut()));
+    *i.body_mut() = body;
 
-    ExitCode::SUCCESS
-}
+    // before the program can redefine it
+    #[cfg(feature = "enable_os")]
+    let exit = i.resolve_definition("exit").cloned();
 
+    let res = if opts.debug {
+        eprintln!("Debugging. Type help for commands.");
+        Debugger::default().run(&mut i, &mut io::stdin().lock(), &mut io::stderr())
+    } else {
+        i.run()
+    };
+    let res = match res {
+        // finished, so exit as if by exit to run the exit hooks
+        #[cfg(feature = "enable_os")]
+        Ok(()) if exit.is_some() => {
+            let status = stack_exit_status(&mut i);
+            i.stack_push(status as i64);
+            i.eval_next(exit.unwrap()).and_then(|()| i.run())
+        },
+        Ok(()) => return ExitCode::from(stack_exit_status(&mut i)),
+        Err(e) => Err(e),
+    };
+    let Err(e) = res else { return ExitCode::SUCCESS };
+    #[cfg(feature = "enable_os")]
+    if let Some(Exit(status)) = e.downcast_ref::<Exit>() {
+        return ExitCode::from(Exit(*status).code());
+    }
+    let e = match e.try_downcast::<WorstError>() {
+        Ok(e) => e.into_inner(),
+        // paused at the top level: not much else to do
+        Err(v) => {
+            let frames = i.backtrace_meta_refs().cloned().collect();
+            WorstError::new("pause", v, frames, i.uniques_mut())
+        },
+    };
+    print_error(&mut i, &e);
+    ExitCode::FAILURE
+}
@@ -0,0 +1,270 @@
+//! A [Printer] turns values back into text.
+//!
+//! This is `value->readable`; `value->string` (in `base/ops.w`)
+//! uses it for everything except strings, which it leaves as they are.
+
+use std::any::TypeId;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use crate::base::*;
+use crate::builtins::hashmap::{HashMap, HashSet};
+use crate::builtins::i64map::I64Map;
+use crate::builtins::interpreter::Interp;
+use crate::error::WorstError;
+use crate::interpreter::{Builtin, DefEnv};
+use crate::reader::Brackets;
+
+/// The current settings for printing values as text.
+///
+/// Readable values (bools, i64s, bigints, f64s, strings, bytevectors, symbols
+/// and lists of them) are printed so that [read_all](crate::reader::read_all)
+/// reads them back as equal values (f64s print as `NaN`, `inf` and `-inf` too).
+/// Symbols are printed as their names, so one whose name would read as
+/// something else (like `1` or `a b` from `string->symbol`) won't read back.
+/// Type-ids print as the name they were given (like `<i64>`) or `<type>`,
+/// which read back as symbols that may be defined to the type.
+/// Anything else, including rationals, is printed `<like this>`.
+#[derive(Default, Clone)]
+pub struct Printer {
+    // used to find type names, if present
+    uniques: Option<UniqueGen>,
+    width: Option<usize>,
+}
+
+impl Printer {
+
+    /// Create a printer that can print the names of types
+    /// (see [add_const_type_builtin](crate::builtins::util::add_const_type_builtin))
+    /// and lists with the [Brackets] they were read with.
+    /// Use the [UniqueGen] from the [Interpreter](crate::interpreter::Interpreter) the values came from.
+    pub fn new(uniques: &UniqueGen) -> Self {
+        Printer { uniques: Some(uniques.clone()), ..Default::default() }
+    }
+
+    /// Wrap lists that would go over `width` columns,
+    /// putting each item on its own indented line.
+    /// With [None] (the default), everything is printed on one line.
+    pub fn set_width(&mut self, width: Option<usize>) {
+        self.width = width;
+    }
+
+    /// Print a value to a string.
+    pub fn print(&self, v: &Val) -> String {
+        let mut out = String::new();
+        self.write(v, self.width.map(|_| 0), &mut vec![], &mut out);
+        out
+    }
+
+    // Print v to out. indent is the current column when wrapping,
+    // and places are the addresses of any Place being printed (to avoid cycles).
+    fn write(&self, v: &Val, indent: Option<usize>, places: &mut Vec<usize>, out: &mut String) {
+        if let Some(v) = v.downcast_ref::<bool>() {
+            out.push_str(if *v { "#t" } else { "#f" });
+        } else if let Some(v) = v.downcast_ref::<i64>() {
+            out.push_str(&v.to_string());
+        } else if let Some(v) = v.downcast_ref::<BigInt>() {
+            out.push_str(&v.to_string());
+        } else if let Some(v) = v.downcast_ref::<BigRational>() {
+            // n/d on its own would read back as a symbol
+            out.push_str(&format!("<rational {v}>"));
+        } else if let Some(v) = v.downcast_ref::<f64>() {
+            // Debug keeps the .0 so it doesn't read back as an i64
+            out.push_str(&format!("{v:?}"));
+        } else if let Some(v) = v.downcast_ref::<String>() {
+            write_string(v, out);
+        } else if let Some(v) = v.downcast_ref::<Symbol>() {
+            out.push_str(v.as_ref());
+        } else if let Some(l) = v.downcast_ref::<List>() {
+            let brackets = self.uniques.as_ref()
+                .and_then(UniqueGen::lookup_type::<Brackets>)
+                .and_then(|u| v.meta_ref().get_ref::<Brackets>(&u).copied())
+                .unwrap_or(Brackets::Round);
+            self.write_list(l, brackets, indent, places, out);
+        } else if let Some(v) = v.downcast_ref::<Vec<u8>>() {
+            out.push_str("#b");
+            write_bytes(v, out);
+        } else if v.is::<TypeId>() {
+            let name = self.uniques.as_ref()
+                .and_then(UniqueGen::lookup_type::<String>)
+                .and_then(|u| v.meta_ref().get_ref::<String>(&u).cloned());
+            out.push_str(name.as_deref().unwrap_or("<type>"));
+        } else if let Some(p) = v.downcast_ref::<Place>() {
+            if places.contains(&p.addr()) {
+                out.push_str("<place ...>");
+            } else {
+                out.push_str("<place ");
+                places.push(p.addr());
+                self.write(&p.get(), indent.map(|i| i + 4), places, out);
+                places.pop();
+                out.push('>');
+            }
+        } else if let Some(e) = v.downcast_ref::<WorstError>() {
+            out.push_str("<error ");
+            out.push_str(e.kind().as_ref());
+            if !e.payload().downcast_ref::<List>().is_some_and(List::is_empty) {
+                out.push(' ');
+                self.write(e.payload(), indent.map(|i| i + 4), places, out);
+            }
+            out.push('>');
+        } else if let Some(d) = v.downcast_ref::<DefEnv>() {
+            out.push_str(&format!("<defenv {} definitions>", d.iter().count()));
+        } else if v.is::<Unique>() {
+            out.push_str("<unique>");
+        } else if v.is::<Builtin>() {
+            out.push_str("<builtin>");
+        } else if v.is::<Interp>() {
+            out.push_str("<interpreter>");
+        } else if v.is::<I64Map>() {
+            out.push_str("<i64map>");
+        } else if v.is::<HashMap>() {
+            out.push_str("<hashmap>");
+        } else if v.is::<HashSet>() {
+            out.push_str("<hashset>");
+        } else {
+            out.push_str("<value>");
+        }
+    }
+
+    fn write_list(&self, l: &List, brackets: Brackets, indent: Option<usize>,
+                  places: &mut Vec<usize>, out: &mut String) {
+        let mut flat = String::from(brackets.open());
+        for (n, v) in l.iter().enumerate() {
+            if n > 0 { flat.push(' '); }
+            self.write(v, None, places, &mut flat);
+        }
+        flat.push(brackets.close());
+        let (Some(indent), Some(width)) = (indent, self.width) else {
+            out.push_str(&flat);
+            return;
+        };
+        if l.is_empty() || indent + flat.chars().count() <= width {
+            out.push_str(&flat);
+            return;
+        }
+        out.push(brackets.open());
+        for v in l.iter() {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent + 4));
+            self.write(v, Some(indent + 4), places, out);
+        }
+        out.push('\n');
+        out.push_str(&" ".repeat(indent));
+        out.push(brackets.close());
+    }
+}
+
+// Quote a string, escaping it the way the reader unescapes it.
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        write_char(c, out);
+    }
+    out.push('"');
+}
+
+// Quote a byte string, with \x escapes for anything but printable ASCII.
+fn write_bytes(b: &[u8], out: &mut String) {
+    out.push('"');
+    for b in b.iter() {
+        if b.is_ascii_graphic() || *b == b' ' {
+            write_char(*b as char, out);
+        } else {
+            out.push_str(&format!("\\x{b:02x}"));
+        }
+    }
+    out.push('"');
+}
+
+fn write_char(c: char, out: &mut String) {
+    match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        '\u{1b}' => out.push_str("\\e"),
+        '\0' => out.push_str("\\0"),
+        c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+        c => out.push(c),
+    }
+}
+
+/// Print a value as text using the default printer
+/// (without type names or wrapping).
+pub fn value_to_string(v: &Val) -> String {
+    Printer::default().print(v)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::read_all;
+
+    fn roundtrip(s: &str) -> String {
+        let vals = read_all(&mut s.chars()).unwrap();
+        assert_eq!(vals.len(), 1);
+        let printed = value_to_string(&vals[0]);
+        let again = read_all(&mut printed.chars()).unwrap();
+        assert_eq!(value_to_string(&again[0]), printed);
+        printed
+    }
+
+    #[test]
+    fn print_readable() {
+        assert_eq!(roundtrip("#t"), "#t");
+        assert_eq!(roundtrip("-12"), "-12");
+        assert_eq!(roundtrip("0x1_0000_0000_0000_0000"), "18446744073709551616");
+        assert_eq!(roundtrip("1.0"), "1.0");
+        assert_eq!(roundtrip("1e100"), "1e100");
+        assert_eq!(roundtrip("NaN"), "NaN");
+        assert_eq!(roundtrip("inf"), "inf");
+        assert_eq!(roundtrip("-inf"), "-inf");
+        let nan = read_all(&mut "NaN".chars()).unwrap();
+        assert!(nan[0].downcast_ref::<f64>().unwrap().is_nan());
+        assert_eq!(roundtrip("\"a\\\"b\\\\c\\n\\e\""), "\"a\\\"b\\\\c\\n\\e\"");
+        assert_eq!(roundtrip("[egg (1 2.5) {} \"x\"]"), "(egg (1 2.5) () \"x\")");
+        assert_eq!(roundtrip("\"\\u{7f}\\0\u{e9}\""), "\"\\u{7f}\\0\u{e9}\"");
+        assert_eq!(roundtrip("#b\"a\\\"\\x00\\xff \""), "#b\"a\\\"\\x00\\xff \"");
+        let s = read_all(&mut "\"\\e[1m\"".chars()).unwrap();
+        assert_eq!(s[0].downcast_ref::<String>().unwrap(), "\u{1b}[1m");
+    }
+
+    #[test]
+    fn print_brackets() {
+        let uniques = UniqueGen::default();
+        let vals = crate::reader::Reader::new(&uniques)
+            .read_all(&mut "[egg {1 (2)}]".chars()).unwrap();
+        assert_eq!(Printer::new(&uniques).print(&vals[0]), "[egg {1 (2)}]");
+        let mut p = Printer::new(&uniques);
+        p.set_width(Some(12));
+        assert_eq!(p.print(&vals[0]), "[\n    egg\n    {1 (2)}\n]");
+    }
+
+    #[test]
+    fn print_unreadable() {
+        assert_eq!(value_to_string(&TypeId::of::<i64>().into()), "<type>");
+        let r = BigRational::new((-1).into(), 3.into());
+        assert_eq!(value_to_string(&r.into()), "<rational -1/3>");
+        let mut uniques = UniqueGen::default();
+        let mut t = Val::from(TypeId::of::<i64>());
+        t.meta_mut().insert_val(uniques.get_type::<String>(), "<i64>".to_string().into());
+        assert_eq!(Printer::new(&uniques).print(&t), "<i64>");
+        // a place containing itself
+        let mut p = Place::wrap(false);
+        p.set(List::from(vec![ p.clone().into() ]));
+        assert_eq!(value_to_string(&p.into()), "<place (<place ...>)>");
+        assert_eq!(value_to_string(&Val::from(DefEnv::default())), "<defenv 0 definitions>");
+    }
+
+    #[test]
+    fn print_wrapped() {
+        let v = read_all(&mut "(define thing (a b c (d e)))".chars()).unwrap();
+        let mut p = Printer::default();
+        p.set_width(Some(20));
+        assert_eq!(p.print(&v[0]), "(\n    define\n    thing\n    (a b c (d e))\n)");
+        p.set_width(Some(14));
+        assert_eq!(p.print(&v[0]), "(\n    define\n    thing\n    (\n        a\n        b\n        c\n        (d e)\n    )\n)");
+        p.set_width(Some(100));
+        assert_eq!(p.print(&v[0]), "(define thing (a b c (d e)))");
+    }
+}
@@ -1,58 +1,302 @@
 
 //! A [Reader] is a little doodad that eats text and poops code.
 
-use std::num::IntErrorKind;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+use num_bigint::BigInt;
 use crate::base::*;
 
-#[cfg(feature = "wasm")]
+// wasm-bindgen is only there for wasm32, and build.rs uses this too
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 use wasm_bindgen::prelude::*;
 
 /// The current state of reading some code.
-#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(all(feature = "wasm", target_arch = "wasm32"), wasm_bindgen)]
 #[derive(Default, Clone)]
 pub struct Reader {
     lists: Vec<ListState>,
     state: BasicState,
+    // where the next char will be read from
+    pos: Position,
+    // where the current atom, string or hash started
+    start: Position,
+    // used to attach a SourceSpan to everything read, if present
+    uniques: Option<UniqueGen>,
+    source: Option<Rc<str>>,
+    hash_handlers: HashMap<char, HashHandler>,
+    // hashes waiting for the next value, innermost last
+    pending: Vec<PendingHash>,
 }
 impl Value for Reader {}
 
+/// What a [HashHandler] gives back: the value to read, if any.
+pub type HashResult = Result<Option<Val>, String>;
+
+/// What to do when reading `#` followed by a particular character.
+/// See [Reader::set_hash_handler].
+///
+/// Handlers return the value to read in place of the `#` and what follows,
+/// or [None] to read nothing (e.g. for a comment).
+#[derive(Clone)]
+pub struct HashHandler {
+    datum: bool,
+    f: Rc<HashFn>,
+}
+// the rest of the atom, and the next value if it's a datum handler
+type HashFn = dyn Fn(&str, Option<Val>) -> HashResult;
+
+impl HashHandler {
+    /// A handler given the rest of the atom after `#c`,
+    /// e.g. `1F` in `#x1F`, or an empty string in `#x 1`.
+    pub fn atom(f: impl Fn(&str) -> HashResult + 'static) -> Self {
+        HashHandler { datum: false, f: Rc::new(move |token, _| f(token)) }
+    }
+
+    /// A handler given the rest of the atom after `#c`
+    /// and the next value read after that,
+    /// e.g. `8` and `(1 2)` in `#u8(1 2)`.
+    pub fn datum(f: impl Fn(&str, Val) -> HashResult + 'static) -> Self {
+        HashHandler {
+            datum: true,
+            f: Rc::new(move |token, v| f(token, v.expect("hash datum"))),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PendingHash {
+    c: char,
+    token: String,
+    start: Position,
+    // list depth at the #, so only a value read at the same depth is used
+    depth: usize,
+}
+
+/// A line and column in some source text, both starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column number in chars, starting at 1.
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self { Position { line: 1, column: 1 } }
+}
+
+impl Position {
+    fn next(&mut self, s: &mut impl Iterator<Item=char>) -> Option<char> {
+        let c = s.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+}
+
+/// Where a value was read from,
+/// attached as a [Meta] entry to everything a [Reader] reads
+/// (if it was created with [Reader::new]).
+/// `end` is the position just after the last character of the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// The name of the source (e.g. a file path), if it was given.
+    pub source: Option<Rc<str>>,
+    /// Position of the first character.
+    pub start: Position,
+    /// Position just after the last character.
+    pub end: Position,
+}
+impl Value for SourceSpan {}
+
+impl Display for SourceSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let source = self.source.as_deref().unwrap_or("<unknown>");
+        write!(f, "{}:{}:{}", source, self.start.line, self.start.column)
+    }
+}
+
+/// Which brackets a list was read with,
+/// attached as a [Meta] entry to every list a [Reader] reads
+/// (if it was created with [Reader::new]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Brackets {
+    /// `( )`
+    Round,
+    /// `[ ]`
+    Square,
+    /// `{ }`
+    Curly,
+}
+impl Value for Brackets {}
+
+impl Brackets {
+    /// The opening bracket character.
+    pub fn open(&self) -> char {
+        match self { Brackets::Round => '(', Brackets::Square => '[', Brackets::Curly => '{' }
+    }
+    /// The closing bracket character.
+    pub fn close(&self) -> char {
+        match self { Brackets::Round => ')', Brackets::Square => ']', Brackets::Curly => '}' }
+    }
+    /// `round`, `square` or `curly`.
+    pub fn name(&self) -> &'static str {
+        match self { Brackets::Round => "round", Brackets::Square => "square", Brackets::Curly => "curly" }
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 enum BasicState {
     #[default] Space,
     Comment,
     Hash,
+    HashAtom {
+        c: char,
+        token: String,
+    },
     Atom(String),
+    // a string, or a bytevector if bytes
     String {
+        buf: Vec<u8>,
+        bytes: bool,
+        // where the \ was and what's after it so far
+        escape: Option<(Position, String)>,
+    },
+    // after #b, expecting "
+    Bytes,
+    // after #r and this many #s, expecting more or "
+    RawStart(usize),
+    // inside a raw string ending with " and hashes #s
+    RawString {
         buf: String,
-        escaping: bool,
+        hashes: usize,
+        // seen " and this many #s that might be the end
+        closing: Option<usize>,
     },
 }
 
+// How an escape sequence in a string reads so far.
+enum Escaped {
+    More,
+    Char(char),
+    Byte(u8),
+    Invalid,
+}
+
+// Read the escape sequence esc (after the \), if it's finished.
+// Bytes (\x80 and above) are only allowed in byte strings.
+fn escaped(esc: &str, bytes: bool) -> Escaped {
+    let mut chars = esc.chars();
+    let rest = || esc.get(1..).unwrap_or("");
+    match chars.next() {
+        None => Escaped::More,
+        Some('e') => Escaped::Char('\u{1b}'),
+        Some('n') => Escaped::Char('\n'),
+        Some('r') => Escaped::Char('\r'),
+        Some('t') => Escaped::Char('\t'),
+        Some('0') => Escaped::Char('\0'),
+        Some('x') => {
+            if !rest().chars().all(|c| c.is_ascii_hexdigit()) {
+                return Escaped::Invalid;
+            }
+            if rest().len() < 2 {
+                return Escaped::More;
+            }
+            match u8::from_str_radix(rest(), 16) {
+                Ok(b) if bytes => Escaped::Byte(b),
+                Ok(b) if b < 0x80 => Escaped::Char(b as char),
+                _ => Escaped::Invalid,
+            }
+        },
+        Some('u') => {
+            if rest().is_empty() {
+                return Escaped::More;
+            }
+            let Some(hex) = rest().strip_prefix('{') else { return Escaped::Invalid };
+            let (hex, done) = match hex.strip_suffix('}') {
+                Some(hex) => (hex, true),
+                None => (hex, false),
+            };
+            if hex.len() > 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                Escaped::Invalid
+            } else if !done {
+                Escaped::More
+            } else {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                    .map_or(Escaped::Invalid, Escaped::Char)
+            }
+        },
+        Some(c) => Escaped::Char(c),
+    }
+}
+
 #[derive(Clone)]
 struct ListState {
-    begin: char,
-    end: char,
+    brackets: Brackets,
+    start: Position,
     data: Vec<Val>,
 }
 
 /// Various ways parsing could fail.
 #[derive(Debug, Clone)]
 pub enum ReadError {
-    /// An odd number of `"`
+    /// An odd number of `"`, or a raw string without its end
     UnclosedString,
-    /// A `#` right at the end of input
+    /// A `\` in a string followed by something unexpected,
+    /// at the position of the `\`
+    InvalidEscape(Position, String),
+    /// A `#` right at the end of input,
+    /// or without the value its [HashHandler] needs
     UnmatchedHash,
     /// A `#` followed by something unexpected
     UnknownHash(char),
+    /// A [HashHandler] didn't like what it was given
+    InvalidHash(char, String),
     /// An unbalanced list delimiter
     UnmatchedList(char),
     /// A number that looked like it was but isn't
     UnparseableNumber(String),
+    /// Something in single quotes that isn't a character
+    UnparseableCharacter(String),
 }
 impl Value for ReadError {}
 
 impl Reader {
 
+    /// Create a reader that attaches a [SourceSpan] to every value it reads,
+    /// as a [Meta] entry keyed by [UniqueGen::get_type].
+    /// Use the [UniqueGen] from the [Interpreter](crate::interpreter::Interpreter)
+    /// that will be evaluating the code.
+    pub fn new(uniques: &UniqueGen) -> Self {
+        Reader { uniques: Some(uniques.clone()), ..Default::default() }
+    }
+
+    /// Set the name (e.g. file path) given in every [SourceSpan] from now on.
+    pub fn set_source_name(&mut self, name: impl AsRef<str>) {
+        self.source = Some(Rc::from(name.as_ref()));
+    }
+
+    /// Read `#c` using the given handler from now on.
+    /// This takes precedence over the usual `#t`, `#f` and `#!`.
+    pub fn set_hash_handler(&mut self, c: char, handler: HashHandler) {
+        self.hash_handlers.insert(c, handler);
+    }
+
+    /// Read an entire piece of text using this reader.
+    pub fn read_all(mut self, src: &mut impl Iterator<Item=char>) -> Result<Vec<Val>, ReadError> {
+        let mut acc = vec![];
+        self.read_into(src, &mut acc)?;
+        if let Some(v) = self.complete()? {
+            acc.push(v);
+        }
+        Ok(acc)
+    }
+
     /// Read some code using this reader into the given accumulator vector.
     /// This may be a partial chunk of code; use [complete] to wrap up at the end.
     pub fn read_into(&mut self, mut s: impl Iterator<Item=char>, acc: &mut Vec<Val>) -> Result<(), ReadError> {
@@ -60,12 +304,14 @@ impl Reader {
             match &mut self.state {
                 BasicState::Space =>
                     'space: loop {
-                        match s.next() {
+                        let here = self.pos;
+                        match self.pos.next(&mut s) {
                             None => break 'top,
                             Some(c) =>
                                 if !c.is_whitespace() {
+                                    self.start = here;
                                     if let Some(list) = self.read_char_state(c)? {
-                                        self.emit(list.into(), acc);
+                                        self.emit(list, acc)?;
                                     }
                                     break 'space;
                                 },
@@ -73,7 +319,7 @@ impl Reader {
                     },
                 BasicState::Comment =>
                     'comment: loop {
-                        match s.next() {
+                        match self.pos.next(&mut s) {
                             None => break 'top,
                             Some('\n') => {
                                 self.state = BasicState::Space;
@@ -83,28 +329,61 @@ impl Reader {
                         }
                     },
                 BasicState::Hash =>
-                    match s.next() {
+                    match self.pos.next(&mut s) {
                         None => break 'top,
+                        Some(c) if self.hash_handlers.contains_key(&c) => {
+                            self.state = BasicState::HashAtom { c, token: String::new() };
+                        },
                         Some('!') => {
                             self.state = BasicState::Comment;
                         },
+                        Some('b') => {
+                            self.state = BasicState::Bytes;
+                        },
+                        Some('r') => {
+                            self.state = BasicState::RawStart(0);
+                        },
                         Some(c@('t' | 'f')) => {
-                            self.emit((c == 't').into(), acc);
+                            let v = self.spanned((c == 't').into(), self.pos);
                             self.state = BasicState::Space;
+                            self.emit(v, acc)?;
                         },
                         Some(c) => return Err(ReadError::UnknownHash(c)),
                     },
+                BasicState::HashAtom { c, token } =>
+                    'hash: loop {
+                        let here = self.pos;
+                        match self.pos.next(&mut s) {
+                            None => break 'top,
+                            Some(d) if d.is_whitespace() || is_delimiter(d) => {
+                                let (c, token) = (*c, std::mem::take(token));
+                                self.state = BasicState::Space;
+                                self.hash(c, token, here, acc)?;
+                                if !d.is_whitespace() {
+                                    self.start = here;
+                                    if let Some(list) = self.read_char_state(d)? {
+                                        self.emit(list, acc)?;
+                                    }
+                                }
+                                break 'hash;
+                            },
+                            Some(d) => token.push(d),
+                        }
+                    },
                 BasicState::Atom(a) =>
                     'atom: loop {
-                        match s.next() {
+                        let here = self.pos;
+                        match self.pos.next(&mut s) {
                             None => break 'top,
-                            Some(c@(';' | '"' | '(' | ')' | '[' | ']' | '{' | '}')) => {
+                            Some(c) if is_delimiter(c) => {
                                 // TODO no clone here
                                 let v = a.clone();
                                 self.state = BasicState::Space;
-                                self.emit(parse_atom(v)?, acc);
+                                let v = self.spanned(parse_atom(v)?, here);
+                                self.emit(v, acc)?;
+                                self.start = here;
                                 if let Some(list) = self.read_char_state(c)? {
-                                    self.emit(list.into(), acc);
+                                    self.emit(list, acc)?;
                                 }
                                 break 'atom;
                             },
@@ -112,40 +391,96 @@ impl Reader {
                                 if c.is_whitespace() {
                                     let v = a.clone();
                                     self.state = BasicState::Space;
-                                    self.emit(parse_atom(v)?, acc);
+                                    let v = self.spanned(parse_atom(v)?, here);
+                                    self.emit(v, acc)?;
                                     break 'atom;
                                 } else {
                                     a.push(c);
                                 },
                         }
                     },
-                    BasicState::String { buf, escaping } =>
-                        'string: loop {
-                            match s.next() {
-                                None => break 'top,
-                                Some(c) => {
-                                    if *escaping {
-                                        *escaping = false;
-                                        match c {
-                                            'e' => buf.push('\u{1b}'),
-                                            'n' => buf.push('\n'),
-                                            'r' => buf.push('\r'),
-                                            't' => buf.push('\t'),
-                                            c => buf.push(c),
-                                        }
-                                    } else if c == '"' {
-                                        let v = buf.clone();
-                                        self.emit(v.into(), acc);
-                                        self.state = BasicState::Space;
-                                        break 'string;
-                                    } else if c == '\\' {
-                                        *escaping = true;
-                                    } else {
-                                        buf.push(c);
-                                    }
+                BasicState::Bytes =>
+                    match self.pos.next(&mut s) {
+                        None => break 'top,
+                        Some('"') => self.state = BasicState::String {
+                            buf: vec![], bytes: true, escape: None,
+                        },
+                        Some(_) => return Err(ReadError::UnknownHash('b')),
+                    },
+                BasicState::RawStart(hashes) =>
+                    match self.pos.next(&mut s) {
+                        None => break 'top,
+                        Some('#') => *hashes += 1,
+                        Some('"') => self.state = BasicState::RawString {
+                            buf: String::new(), hashes: *hashes, closing: None,
+                        },
+                        Some(_) => return Err(ReadError::UnknownHash('r')),
+                    },
+                BasicState::RawString { buf, hashes, closing } =>
+                    'raw: loop {
+                        let Some(c) = self.pos.next(&mut s) else { break 'top };
+                        if let Some(n) = *closing {
+                            if c == '#' {
+                                *closing = Some(n + 1);
+                            } else {
+                                // it wasn't the end after all
+                                buf.push('"');
+                                buf.extend(std::iter::repeat_n('#', n));
+                                *closing = None;
+                            }
+                        }
+                        if closing.is_none() {
+                            if c == '"' {
+                                *closing = Some(0);
+                            } else {
+                                buf.push(c);
+                            }
+                        }
+                        if *closing == Some(*hashes) {
+                            let v = std::mem::take(buf);
+                            let v = self.spanned(v.into(), self.pos);
+                            self.state = BasicState::Space;
+                            self.emit(v, acc)?;
+                            break 'raw;
+                        }
+                    },
+                BasicState::String { buf, bytes, escape } =>
+                    'string: loop {
+                        let here = self.pos;
+                        let Some(c) = self.pos.next(&mut s) else { break 'top };
+                        if let Some((start, esc)) = escape {
+                            esc.push(c);
+                            match escaped(esc, *bytes) {
+                                Escaped::More => {},
+                                Escaped::Char(c) => {
+                                    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                                    *escape = None;
+                                },
+                                Escaped::Byte(b) => {
+                                    buf.push(b);
+                                    *escape = None;
                                 },
+                                Escaped::Invalid =>
+                                    return Err(ReadError::InvalidEscape(*start, format!("\\{esc}"))),
                             }
-                        },
+                        } else if c == '"' {
+                            let buf = std::mem::take(buf);
+                            let v = if *bytes {
+                                Val::from(buf)
+                            } else {
+                                // only whole chars and ASCII bytes went in
+                                Val::from(String::from_utf8(buf).unwrap())
+                            };
+                            let v = self.spanned(v, self.pos);
+                            self.state = BasicState::Space;
+                            self.emit(v, acc)?;
+                            break 'string;
+                        } else if c == '\\' {
+                            *escape = Some((here, String::new()));
+                        } else {
+                            buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                    },
             }
         }
         Ok(())
@@ -156,49 +491,120 @@ impl Reader {
     /// or perhaps there's an atom or number at the very end of the file.
     pub fn complete(mut self) -> Result<Option<Val>, ReadError> {
         if let Some(ls) = self.lists.pop() {
-            return Err(ReadError::UnmatchedList(ls.begin));
+            return Err(ReadError::UnmatchedList(ls.brackets.open()));
+        }
+        let mut acc = vec![];
+        match std::mem::take(&mut self.state) {
+            BasicState::Space | BasicState::Comment => {},
+            BasicState::Hash => return Err(ReadError::UnmatchedHash),
+            BasicState::HashAtom { c, token } => {
+                let end = self.pos;
+                self.hash(c, token, end, &mut acc)?;
+            },
+            BasicState::Atom(a) => {
+                let v = self.spanned(parse_atom(a)?, self.pos);
+                self.emit(v, &mut acc)?;
+            },
+            BasicState::Bytes => return Err(ReadError::UnmatchedHash),
+            BasicState::String { .. } | BasicState::RawStart(_) | BasicState::RawString { .. } =>
+                return Err(ReadError::UnclosedString),
         }
-        match self.state {
-            BasicState::Space | BasicState::Comment => Ok(None),
-            BasicState::Hash => Err(ReadError::UnmatchedHash),
-            BasicState::Atom(a) => Ok(Some(parse_atom(a)?)),
-            BasicState::String { .. } => Err(ReadError::UnclosedString),
+        if !self.pending.is_empty() {
+            return Err(ReadError::UnmatchedHash);
         }
+        Ok(acc.pop())
+    }
+
+    // Finished reading #c and the rest of its atom, ending at end:
+    // either read a value right now or wait for the next one.
+    fn hash(&mut self, c: char, token: String, end: Position, out: &mut Vec<Val>) -> Result<(), ReadError> {
+        let handler = self.hash_handlers.get(&c).cloned().ok_or(ReadError::UnknownHash(c))?;
+        if handler.datum {
+            let depth = self.lists.len();
+            self.pending.push(PendingHash { c, token, start: self.start, depth });
+        } else if let Some(v) = (handler.f)(&token, None).map_err(|e| ReadError::InvalidHash(c, e))? {
+            let v = self.spanned(v, end);
+            self.emit(v, out)?;
+        }
+        Ok(())
+    }
+
+    // Attach a SourceSpan from self.start to end, if enabled.
+    fn spanned(&mut self, mut v: Val, end: Position) -> Val {
+        self.add_span(&mut v, self.start, end);
+        v
     }
 
-    fn emit(&mut self, v: Val, out: &mut Vec<Val>) {
+    fn add_span(&mut self, v: &mut Val, start: Position, end: Position) {
+        if let Some(uniques) = &mut self.uniques {
+            let span = SourceSpan { source: self.source.clone(), start, end };
+            v.meta_mut().insert_val(uniques.get_type::<SourceSpan>(), span.into());
+        }
+    }
+
+    // Where v ends, if it has a SourceSpan.
+    fn span_end(&self, v: &Val) -> Option<Position> {
+        let u = self.uniques.as_ref()?.lookup_type::<SourceSpan>()?;
+        v.meta_ref().get_ref::<SourceSpan>(&u).map(|s| s.end)
+    }
+
+    fn emit(&mut self, mut v: Val, out: &mut Vec<Val>) -> Result<(), ReadError> {
+        // give it to any hashes waiting for it first
+        while self.pending.last().is_some_and(|p| p.depth == self.lists.len()) {
+            let p = self.pending.pop().unwrap();
+            let handler = self.hash_handlers.get(&p.c).cloned()
+                .ok_or(ReadError::UnknownHash(p.c))?;
+            let end = self.span_end(&v);
+            match (handler.f)(&p.token, Some(v)).map_err(|e| ReadError::InvalidHash(p.c, e))? {
+                Some(r) => v = r,
+                None => return Ok(()),
+            }
+            if let Some(end) = end {
+                self.add_span(&mut v, p.start, end);
+            }
+        }
         if let Some(ls) = self.lists.last_mut() {
             ls.data.push(v);
         } else {
             out.push(v);
         }
+        Ok(())
     }
 
-    fn start_list(&mut self, begin: char, end: char) {
-        self.lists.push(ListState { begin, end, data: vec![], });
+    fn start_list(&mut self, brackets: Brackets) {
+        let start = self.start;
+        self.lists.push(ListState { brackets, start, data: vec![], });
     }
-    fn end_list(&mut self, c: char) -> Result<List, ReadError> {
+    fn end_list(&mut self, c: char) -> Result<Val, ReadError> {
+        if self.pending.last().is_some_and(|p| p.depth == self.lists.len()) {
+            return Err(ReadError::UnmatchedHash);
+        }
         if let Some(ls) = self.lists.pop() {
-            if c == ls.end {
-                Ok(List::from(ls.data))
+            if c == ls.brackets.close() {
+                let mut l = Val::from(List::from(ls.data));
+                self.add_span(&mut l, ls.start, self.pos);
+                if let Some(uniques) = &mut self.uniques {
+                    l.meta_mut().insert_val(uniques.get_type::<Brackets>(), ls.brackets.into());
+                }
+                Ok(l)
             } else {
-                Err(ReadError::UnmatchedList(ls.begin))
+                Err(ReadError::UnmatchedList(ls.brackets.open()))
             }
         } else {
             Err(ReadError::UnmatchedList(c))
         }
     }
 
-    fn read_char_state(&mut self, c: char) -> Result<Option<List>, ReadError> {
+    fn read_char_state(&mut self, c: char) -> Result<Option<Val>, ReadError> {
         match c {
             ';' => self.state = BasicState::Comment,
             '"' => self.state = BasicState::String {
-                buf: "".to_string(), escaping: false,
+                buf: vec![], bytes: false, escape: None,
             },
             '#' => self.state = BasicState::Hash,
-            '(' => self.start_list('(', ')'),
-            '[' => self.start_list('[', ']'),
-            '{' => self.start_list('{', '}'),
+            '(' => self.start_list(Brackets::Round),
+            '[' => self.start_list(Brackets::Square),
+            '{' => self.start_list(Brackets::Curly),
             ')' | ']' | '}' => return Ok(Some(self.end_list(c)?)),
             c => self.state = BasicState::Atom(c.into()),
         }
@@ -206,29 +612,121 @@ impl Reader {
     }
 }
 
+fn is_delimiter(c: char) -> bool {
+    matches!(c, ';' | '"' | '(' | ')' | '[' | ']' | '{' | '}')
+}
+
+// Atoms are characters, numbers, or otherwise symbols.
 fn parse_atom(s: String) -> Result<Val, ReadError> {
-    match str::parse::<i64>(&s) {
-        Ok(v) => Ok(v.into()),
-        Err(e) if e.kind() == &IntErrorKind::PosOverflow
-            || e.kind() == &IntErrorKind::NegOverflow =>
-            Err(ReadError::UnparseableNumber(s)),
-        Err(_) =>
-            match str::parse::<f64>(&s) {
-                Ok(v) => Ok(v.into()),
-                Err(_) => Ok(Symbol::from(s).into()),
+    if s.len() >= 3 && s.starts_with('\'') && s.ends_with('\'') {
+        return match parse_char(&s[1..s.len() - 1]) {
+            Some(c) => Ok((c as i64).into()),
+            None => Err(ReadError::UnparseableCharacter(s)),
+        };
+    }
+    match parse_number(&s) {
+        Ok(Some(v)) => Ok(v),
+        Ok(None) => Ok(Symbol::from(s).into()),
+        Err(()) => Err(ReadError::UnparseableNumber(s)),
+    }
+}
+
+// The inside of a character literal: one character or an escape,
+// like in strings with a few extras: \0, \s (space), \' and \u then hex digits
+// (not \u{...} since braces would end the atom).
+fn parse_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    let c = match chars.next()? {
+        '\\' => match chars.next()? {
+            'e' => '\u{1b}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            's' => ' ',
+            'u' => {
+                let hex = chars.as_str();
+                chars = "".chars();
+                char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
             },
+            c => c,
+        },
+        c => c,
+    };
+    if chars.next().is_some() { None } else { Some(c) }
+}
+
+// Remove _ separators, which must be between two digits
+// (of the given radix, or decimal digits if None).
+fn strip_separators(s: &str, radix: u32) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut prev = None;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '_' {
+            let next = chars.peek().copied();
+            if !prev.is_some_and(|p: char| p.is_digit(radix))
+                || !next.is_some_and(|n| n.is_digit(radix)) {
+                return None;
+            }
+        } else {
+            out.push(c);
+        }
+        prev = Some(c);
     }
+    Some(out)
+}
+
+// An integer in the given radix, as a BigInt if it's too big for an i64.
+fn parse_integer(sign: &str, digits: &str, radix: u32) -> Result<Val, ()> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(());
+    }
+    let s = format!("{sign}{digits}");
+    match i64::from_str_radix(&s, radix) {
+        Ok(n) => Ok(n.into()),
+        Err(_) => BigInt::parse_bytes(s.as_bytes(), radix).map(Val::from).ok_or(()),
+    }
+}
+
+// Ok(None) if it's not a number at all,
+// Err if it looks like a number but isn't one.
+fn parse_number(s: &str) -> Result<Option<Val>, ()> {
+    let (sign, rest) = match s.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", s.strip_prefix('+').unwrap_or(s)),
+    };
+    let radix = match rest.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0o" | "0O") => Some(8),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        let digits = strip_separators(&rest[2..], radix).ok_or(())?;
+        return parse_integer(sign, &digits, radix).map(Some);
+    }
+    let mut start = rest.chars();
+    let numeric = match start.next() {
+        Some(c) if c.is_ascii_digit() => true,
+        Some('.') => start.next().is_some_and(|c| c.is_ascii_digit()),
+        _ => false,
+    };
+    if !numeric {
+        // inf, NaN and so on
+        return Ok(str::parse::<f64>(s).ok().map(Val::from));
+    }
+    let Some(rest) = strip_separators(rest, 10) else { return Ok(None) };
+    if rest.chars().all(|c| c.is_ascii_digit()) {
+        return parse_integer(sign, &rest, 10).map(Some);
+    }
+    // 1.5, 1e10, 2.5e-3 etc, otherwise a symbol that starts with a digit
+    Ok(str::parse::<f64>(&format!("{sign}{rest}")).ok().map(Val::from))
 }
 
 /// Read an entire piece of text as Worst values using the default reader.
 pub fn read_all(src: &mut impl Iterator<Item=char>) -> Result<Vec<Val>, ReadError> {
-    let mut reader = Reader::default();
-    let mut acc = vec![];
-    reader.read_into(src, &mut acc)?;
-    if let Some(v) = reader.complete()? {
-        acc.push(v);
-    }
-    Ok(acc)
+    Reader::default().read_all(src)
 }
 
 // TODO fix tests or move into worst
@@ -262,9 +760,91 @@ mod tests {
                     vec!["egg", "blub\nbo", "", "ok\"ok"]);
     }
 
+    #[test]
+    fn read_string_escapes() {
+        assert_eq!(vec_read::<String>("\"\\u{41}\\u{1F600}\\x41\\x7f\\0\\q\\\\\""),
+                    vec!["A\u{1f600}A\u{7f}\0q\\"]);
+        let bad = |s: &str| match read_all(&mut s.chars()) {
+            Err(ReadError::InvalidEscape(p, e)) => ((p.line, p.column), e),
+            r => panic!("{s}: {:?}", r.map(|v| v.len())),
+        };
+        assert_eq!(bad("\"ok\n  \\x8f\""), ((2, 3), "\\x8f".into()));
+        assert_eq!(bad("\"\\xg0\""), ((1, 2), "\\xg".into()));
+        assert_eq!(bad("\"\\u41\""), ((1, 2), "\\u4".into()));
+        assert_eq!(bad(" \"\\u{110000}\""), ((1, 3), "\\u{110000}".into()));
+        assert_eq!(bad("\"\\u{1234567}\""), ((1, 2), "\\u{1234567".into()));
+        assert_eq!(bad("\"\\u{é}\""), ((1, 2), "\\u{é".into()));
+        assert!(matches!(read_all(&mut "\"\\u{41".chars()), Err(ReadError::UnclosedString)));
+    }
+
+    #[test]
+    fn read_raw_string() {
+        assert_eq!(vec_read::<String>("#r\"a\\n\" #r#\"say \"hi\"\n\"#"),
+                    vec!["a\\n", "say \"hi\"\n"]);
+        assert_eq!(vec_read::<String>("#r##\"\"# \"#\"##"), vec!["\"# \"#"]);
+        assert_eq!(vec_read::<String>("#r\"\""), vec![""]);
+        assert!(matches!(read_all(&mut "#r#\"a\"".chars()), Err(ReadError::UnclosedString)));
+        assert!(matches!(read_all(&mut "#rx".chars()), Err(ReadError::UnknownHash('r'))));
+    }
+
+    #[test]
+    fn read_bytes() {
+        assert_eq!(vec_read::<Vec<u8>>("#b\"a\\x00\\xFF\\n\u{e9}\" #b\"\""),
+                    vec![vec![b'a', 0, 255, b'\n', 0xc3, 0xa9], vec![]]);
+        assert!(matches!(read_all(&mut "#bx".chars()), Err(ReadError::UnknownHash('b'))));
+        assert!(matches!(read_all(&mut "#b".chars()), Err(ReadError::UnmatchedHash)));
+    }
+
     #[test]
     fn read_i64() {
         assert_eq!(vec_read::<i64>("123"), vec![123]);
+        assert_eq!(vec_read::<i64>("-5 +5 -9223372036854775808"), vec![-5, 5, i64::MIN]);
+    }
+
+    #[test]
+    fn read_radix() {
+        assert_eq!(vec_read::<i64>("0x1F 0XfF -0x10 0o17 0b101 -0b1"),
+                    vec![31, 255, -16, 15, 5, -1]);
+        assert_eq!(vec_read::<i64>("-0x8000000000000000"), vec![i64::MIN]);
+        for bad in ["0x", "0xg", "0b2", "0o8", "0x_1", "0x1_", "-0x"] {
+            assert!(matches!(read_all(&mut bad.chars()), Err(ReadError::UnparseableNumber(_))),
+                    "{bad}");
+        }
+    }
+
+    #[test]
+    fn read_separators() {
+        assert_eq!(vec_read::<i64>("1_000_000 0xFF_FF 0b1_0 -1_2"), vec![1000000, 0xffff, 2, -12]);
+        assert_eq!(vec_read::<f64>("1_000.5 1e1_0 .5 2.5e-3 1E3"), vec![1000.5, 1e10, 0.5, 2.5e-3, 1e3]);
+        assert_eq!(vec_read::<Symbol>("_ a_b 1__0 1_ 1_.5 2d"),
+                    vec!["_", "a_b", "1__0", "1_", "1_.5", "2d"].into_iter()
+                        .map(Symbol::from).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn read_f64() {
+        assert_eq!(vec_read::<f64>("1.5 -2.0 1e100 -1e-5 5."), vec![1.5, -2.0, 1e100, -1e-5, 5.0]);
+        assert!(vec_read::<f64>("NaN")[0].is_nan());
+        assert_eq!(vec_read::<f64>("inf -inf"), vec![f64::INFINITY, f64::NEG_INFINITY]);
+    }
+
+    #[test]
+    fn read_bigint() {
+        let big = |s: &str| BigInt::parse_bytes(s.as_bytes(), 10).unwrap();
+        assert_eq!(vec_read::<BigInt>("9223372036854775808 -9223372036854775809 0x1_0000_0000_0000_0000"),
+                    vec![big("9223372036854775808"), big("-9223372036854775809"),
+                         big("18446744073709551616")]);
+    }
+
+    #[test]
+    fn read_char() {
+        assert_eq!(vec_read::<i64>("'a' 'Z' '\\n' '\\s' '\\'' '\\\\' '\\u1F600' 'é'"),
+                    vec![97, 90, 10, 32, 39, 92, 0x1f600, 0xe9]);
+        assert_eq!(vec_read::<Symbol>("' ''"), vec!["'".to_symbol(), "''".to_symbol()]);
+        for bad in ["'ab'", "'\\u110000'", "'\\uzz'", "'\\u'", "'\\'"] {
+            assert!(matches!(read_all(&mut bad.chars()), Err(ReadError::UnparseableCharacter(_))),
+                    "{bad}");
+        }
     }
 
     #[test]
@@ -274,5 +854,95 @@ mod tests {
                     vec!["time".to_symbol(), "for-some".to_symbol(), ".cool.beans".to_symbol()]);
     }
 
+    #[test]
+    fn read_source_span() {
+        let mut uniques = UniqueGen::default();
+        let mut reader = Reader::new(&uniques);
+        reader.set_source_name("test.w");
+        let vals = reader.read_all(&mut "egg (1\n  \"two\" #t) 3".chars()).unwrap();
+        let u = uniques.get_type::<SourceSpan>();
+        let span = |v: &Val| {
+            let s = v.meta_ref().get_ref::<SourceSpan>(&u).unwrap();
+            assert_eq!(s.source.as_deref(), Some("test.w"));
+            ((s.start.line, s.start.column), (s.end.line, s.end.column))
+        };
+        assert_eq!(vals.len(), 3);
+        assert_eq!(span(&vals[0]), ((1, 1), (1, 4)));
+        assert_eq!(span(&vals[1]), ((1, 5), (2, 12)));
+        assert_eq!(span(&vals[2]), ((2, 13), (2, 14)));
+        let l = vals[1].downcast_ref::<List>().unwrap();
+        assert_eq!(span(l.get(0).unwrap()), ((1, 6), (1, 7)));
+        assert_eq!(span(l.get(1).unwrap()), ((2, 3), (2, 8)));
+        assert_eq!(span(l.get(2).unwrap()), ((2, 9), (2, 11)));
+    }
+
+    #[test]
+    fn read_brackets() {
+        let mut uniques = UniqueGen::default();
+        let vals = Reader::new(&uniques).read_all(&mut "(a [b {}]) 1".chars()).unwrap();
+        let u = uniques.get_type::<Brackets>();
+        let brackets = |v: &Val| v.meta_ref().get_ref::<Brackets>(&u).copied();
+        assert_eq!(brackets(&vals[0]), Some(Brackets::Round));
+        assert_eq!(brackets(&vals[1]), None);
+        let inner = vals[0].downcast_ref::<List>().unwrap().get(1).unwrap();
+        assert_eq!(brackets(inner), Some(Brackets::Square));
+        let innermost = inner.downcast_ref::<List>().unwrap().get(1).unwrap();
+        assert_eq!(brackets(innermost), Some(Brackets::Curly));
+        assert!(matches!(read_all(&mut "(a]".chars()), Err(ReadError::UnmatchedList('('))));
+    }
+
+    #[test]
+    fn read_hash_handlers() {
+        let mut reader = Reader::default();
+        reader.set_hash_handler('x', HashHandler::atom(|token| {
+            i64::from_str_radix(token, 16).map(|n| Some(n.into())).map_err(|e| e.to_string())
+        }));
+        reader.set_hash_handler(';', HashHandler::datum(|_, _| Ok(None)));
+        reader.set_hash_handler('u', HashHandler::datum(|token, v| {
+            if token != "8" { return Err("only #u8".into()); }
+            let l = v.downcast_ref::<List>().ok_or("not a list")?;
+            l.iter().map(|v| v.downcast_ref::<i64>().map(|n| *n as u8))
+                .collect::<Option<Vec<u8>>>().map(|b| Some(b.into())).ok_or("not a byte".into())
+        }));
+        let read = |s: &str| reader.clone().read_all(&mut s.chars());
+
+        let vals = read("#x1F (#xff) #t").unwrap();
+        assert_eq!(vals[0].downcast_ref::<i64>(), Some(&31));
+        assert_eq!(vals[1].downcast_ref::<List>().unwrap().get(0).unwrap().downcast_ref::<i64>(), Some(&255));
+        assert_eq!(vals[2].downcast_ref::<bool>(), Some(&true));
+        assert!(matches!(read("#xg"), Err(ReadError::InvalidHash('x', _))));
+
+        let vals = read("1 #; 2 (3 #;(4 5) #; #; 6 7) #;8").err();
+        assert!(matches!(vals, Some(ReadError::UnmatchedHash)));
+        let vals = read("1 #; 2 (3 #;(4 5) #; #; 6 7 8)").unwrap();
+        assert_eq!(vals.len(), 2);
+        assert_eq!(vals[1].downcast_ref::<List>().unwrap().len(), 2);
+        assert!(matches!(read("(1 #;)"), Err(ReadError::UnmatchedHash)));
+
+        let vals = read("#u8(1 2 255) #u8[]").unwrap();
+        assert_eq!(vals[0].downcast_ref::<Vec<u8>>(), Some(&vec![1, 2, 255]));
+        assert_eq!(vals[1].downcast_ref::<Vec<u8>>(), Some(&vec![]));
+        assert!(matches!(read("#u16(1)"), Err(ReadError::InvalidHash('u', _))));
+        assert!(matches!(read("#q"), Err(ReadError::UnknownHash('q'))));
+    }
+
+    #[test]
+    fn read_hash_span() {
+        let mut uniques = UniqueGen::default();
+        let mut reader = Reader::new(&uniques);
+        reader.set_hash_handler('x', HashHandler::atom(|token| {
+            i64::from_str_radix(token, 16).map(|n| Some(n.into())).map_err(|e| e.to_string())
+        }));
+        reader.set_hash_handler('q', HashHandler::datum(|_, v| Ok(Some(v))));
+        let vals = reader.read_all(&mut " #xff #q (a b)".chars()).unwrap();
+        let u = uniques.get_type::<SourceSpan>();
+        let span = |v: &Val| {
+            let s = v.meta_ref().get_ref::<SourceSpan>(&u).unwrap();
+            (s.start.column, s.end.column)
+        };
+        assert_eq!(span(&vals[0]), (2, 6));
+        assert_eq!(span(&vals[1]), (7, 15));
+    }
+
 }
 